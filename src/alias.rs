@@ -0,0 +1,110 @@
+use std::path::Path;
+use chrono::{Datelike, NaiveDate};
+
+const PLACEHOLDERS: [&str; 6] = ["year", "month", "day", "slug", "stem", "dir"];
+
+/// The url a post had under hakyll, e.g. `/posts/{year}/{month}/{day}/{slug}/index.html`
+/// for a site routing posts by date.
+#[derive(Debug, Eq, PartialEq)]
+pub struct AliasTemplate(String);
+
+/// What the placeholders of an alias template stand for.
+pub struct AliasContext<'a> {
+  /// The post's date, of which only the leading `YYYY-MM-DD` is used.
+  pub date: Option<&'a str>,
+  pub slug: &'a str,
+  /// File name of the hakyll post without its extension, date prefix and all.
+  pub stem: &'a str,
+  /// Directory of the hakyll post relative to the directory being converted.
+  pub dir: &'a Path,
+}
+
+impl AliasTemplate {
+  pub fn parse(template: &str) -> Result<AliasTemplate, String> {
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+      let end = rest[start..].find('}').ok_or_else(|| format!("unclosed placeholder in {:?}", template))?;
+      let name = &rest[start + 1..start + end];
+      if !PLACEHOLDERS.contains(&name) {
+        return Err(format!("unknown placeholder {{{}}} in {:?}, expected one of {:?}", name, template, PLACEHOLDERS));
+      }
+      rest = &rest[start + end + 1..];
+    }
+    Ok(AliasTemplate(template.to_string()))
+  }
+
+  /// Hakyll's default `setExtension "html"` route for posts copied under `dir`.
+  pub fn from_dir(dir: &Path) -> AliasTemplate {
+    AliasTemplate(format!("{}/{{dir}}/{{stem}}.html", dir.display()))
+  }
+
+  pub fn render(&self, context: &AliasContext) -> Result<String, String> {
+    let mut alias = self.0.clone();
+    if ["{year}", "{month}", "{day}"].iter().any(|placeholder| alias.contains(placeholder)) {
+      let date = context.date
+        .and_then(|date| NaiveDate::parse_from_str(date.get(0..10)?, "%Y-%m-%d").ok())
+        .ok_or_else(|| format!("alias {:?} needs a YYYY-MM-DD date but the post has {:?}", self.0, context.date))?;
+      alias = alias
+        .replace("{year}", &format!("{:04}", date.year()))
+        .replace("{month}", &format!("{:02}", date.month()))
+        .replace("{day}", &format!("{:02}", date.day()));
+    }
+    alias = alias
+      .replace("{slug}", context.slug)
+      .replace("{stem}", context.stem)
+      .replace("{dir}", &context.dir.to_string_lossy());
+    Ok(collapse_slashes(&alias))
+  }
+}
+
+/// An empty `{dir}` leaves `//` behind, which would never match the old url. The `//` of
+/// `https://` is kept.
+fn collapse_slashes(alias: &str) -> String {
+  let path_start = alias.find("://").map_or(0, |idx| idx + 3);
+  let mut collapsed = String::from(&alias[..path_start]);
+  for c in alias[path_start..].chars() {
+    if !(c == '/' && collapsed.ends_with('/')) {
+      collapsed.push(c);
+    }
+  }
+  collapsed
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::Path;
+  use super::{AliasTemplate, AliasContext};
+
+  fn context<'a>(date: Option<&'a str>, dir: &'a Path) -> AliasContext<'a> {
+    AliasContext { date, slug: "bigdata", stem: "2020-02-01-bigdata", dir }
+  }
+
+  #[test]
+  fn test_render_from_dir() {
+    let template = AliasTemplate::from_dir(Path::new("/posts"));
+    assert_eq!(template.render(&context(None, Path::new(""))).unwrap(), "/posts/2020-02-01-bigdata.html");
+    assert_eq!(template.render(&context(None, Path::new("2020"))).unwrap(), "/posts/2020/2020-02-01-bigdata.html");
+  }
+
+  #[test]
+  fn test_render_date() {
+    let template = AliasTemplate::parse("/posts/{year}/{month}/{day}/{slug}/index.html").unwrap();
+    assert_eq!(template.render(&context(Some("2020-02-01T10:00:00+09:00"), Path::new(""))).unwrap(),
+               "/posts/2020/02/01/bigdata/index.html");
+    assert!(template.render(&context(None, Path::new(""))).is_err());
+    assert!(template.render(&context(Some("Feb 1, 2020"), Path::new(""))).is_err());
+  }
+
+  #[test]
+  fn test_render_absolute() {
+    let template = AliasTemplate::parse("https://old.example.com/{dir}/{stem}.html").unwrap();
+    assert_eq!(template.render(&context(None, Path::new(""))).unwrap(), "https://old.example.com/2020-02-01-bigdata.html");
+  }
+
+  #[test]
+  fn test_parse() {
+    assert!(AliasTemplate::parse("/{dir}/{stem}.html").is_ok());
+    assert!(AliasTemplate::parse("/{title}.html").is_err());
+    assert!(AliasTemplate::parse("/{slug.html").is_err());
+  }
+}
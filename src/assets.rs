@@ -0,0 +1,154 @@
+//! Images and files the posts refer to, which hakyll copies from `images/` and `files/`
+//! with `idRoute` and zola wants either in `static/` or next to the page.
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use regex::{Captures, Regex};
+use crate::markdown::{dir_of, is_external, map_text_lines, resolve_dots, url_patterns};
+
+/// Directories of the hakyll site that hold assets rather than pages.
+pub const ASSET_DIRS: [&str; 2] = ["images", "files"];
+
+pub const PLACEMENTS: [&str; 2] = ["static", "bundle"];
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Placement {
+  /// Under zola's `static` directory at the given path, keeping the urls hakyll served
+  /// them at.
+  Static(PathBuf),
+  /// Next to the page, which becomes `<slug>/index.md` so that zola picks them up as
+  /// colocated assets.
+  Bundle,
+}
+
+/// An asset a post refers to.
+#[derive(Debug, Eq, PartialEq)]
+pub struct Asset {
+  /// The file in the hakyll site.
+  pub source: PathBuf,
+  /// Its path relative to the hakyll site, e.g. `images/foo.png`.
+  pub path: String,
+}
+
+impl Placement {
+  /// Where `asset` goes for the post written to `page`.
+  pub fn target(&self, asset: &Asset, page: &Path) -> PathBuf {
+    match self {
+      Placement::Static(dir) => dir.join(&asset.path),
+      Placement::Bundle => page.parent().unwrap().join(&asset.path),
+    }
+  }
+
+  fn url(&self, asset: &Asset) -> String {
+    match self {
+      Placement::Static(_) => format!("/{}", asset.path),
+      Placement::Bundle => asset.path.clone(),
+    }
+  }
+}
+
+/// Find the assets under `site_root` that `body` refers to and point the references at
+/// where `placement` puts them. Relative references are resolved against `base`, the
+/// directory of the post's old url or of the post itself within the site. Returns the
+/// new body, the assets to copy and warnings for references to assets that aren't there.
+pub fn relocate(body: &str, base: Option<&str>, site_root: &Path, placement: &Placement) -> (String, Vec<Asset>, Vec<String>) {
+  let [inline, reference, attribute] = url_patterns();
+  let mut assets = Vec::new();
+  let mut warnings = Vec::new();
+  let mut relocation = Relocation { base, site_root, placement, assets: &mut assets, warnings: &mut warnings };
+  let body = map_text_lines(body, |line| {
+    let line = relocation.replace(inline, line);
+    let line = relocation.replace(reference, &line);
+    relocation.replace(attribute, &line).into_owned()
+  });
+  (body, assets, warnings)
+}
+
+struct Relocation<'a> {
+  base: Option<&'a str>,
+  site_root: &'a Path,
+  placement: &'a Placement,
+  assets: &'a mut Vec<Asset>,
+  warnings: &'a mut Vec<String>,
+}
+
+impl <'a> Relocation<'a> {
+  fn replace<'t>(&mut self, pattern: &Regex, line: &'t str) -> Cow<'t, str> {
+    pattern.replace_all(line, |caps: &Captures| {
+      match self.resolve(&caps[2]) {
+        Some(url) => format!("{}{}{}", &caps[1], url, &caps[3]),
+        None => caps[0].to_string(),
+      }
+    })
+  }
+
+  /// The new url for `url` if it is an asset.
+  fn resolve(&mut self, url: &str) -> Option<String> {
+    if is_external(url) {
+      return None;
+    }
+    let split = url.find(['#', '?']).unwrap_or(url.len());
+    let (path, suffix) = url.split_at(split);
+    let path = match path.strip_prefix('/') {
+      Some(absolute) => absolute.to_string(),
+      None => format!("{}{}", dir_of(self.base?.trim_start_matches('/')), path),
+    };
+    let path = resolve_dots(&path)?;
+    if !ASSET_DIRS.iter().any(|dir| path.starts_with(&format!("{}/", dir))) {
+      return None;
+    }
+    let source = self.site_root.join(&path);
+    if !source.is_file() {
+      self.warnings.push(format!("reference to an asset that doesn't exist: {}", url));
+      return None;
+    }
+    let asset = Asset { source, path };
+    let new_url = format!("{}{}", self.placement.url(&asset), suffix);
+    if !self.assets.contains(&asset) {
+      self.assets.push(asset);
+    }
+    Some(new_url)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::{Path, PathBuf};
+  use super::{relocate, Asset, Placement};
+
+  fn site() -> PathBuf {
+    let root = std::env::temp_dir().join(format!("hakyell2zola-assets-{}", std::process::id()));
+    std::fs::create_dir_all(root.join("images")).unwrap();
+    std::fs::create_dir_all(root.join("files")).unwrap();
+    std::fs::write(root.join("images/a.png"), "").unwrap();
+    std::fs::write(root.join("files/b.pdf"), "").unwrap();
+    root
+  }
+
+  #[test]
+  fn test_relocate() {
+    let root = site();
+    let body = "![a](/images/a.png) [b](../files/b.pdf#page=2)\n<img src=\"/images/a.png\">\n```\n![a](/images/a.png)\n```\n[post](/posts/foo.html)\n";
+    let (static_body, assets, warnings) = relocate(body, Some("/posts/foo.html"), &root, &Placement::Static(PathBuf::from("static")));
+    assert_eq!(static_body, body.replace("../files/b.pdf", "/files/b.pdf"));
+    assert_eq!(assets, vec![
+      Asset { source: root.join("images/a.png"), path: String::from("images/a.png") },
+      Asset { source: root.join("files/b.pdf"), path: String::from("files/b.pdf") },
+    ]);
+    assert!(warnings.is_empty());
+    assert_eq!(Placement::Static(PathBuf::from("static")).target(&assets[0], Path::new("content/posts/foo.md")),
+               Path::new("static/images/a.png"));
+
+    let (bundle_body, _, _) = relocate(body, Some("/posts/foo.html"), &root, &Placement::Bundle);
+    assert_eq!(bundle_body, "![a](images/a.png) [b](files/b.pdf#page=2)\n<img src=\"images/a.png\">\n```\n![a](/images/a.png)\n```\n[post](/posts/foo.html)\n");
+    assert_eq!(Placement::Bundle.target(&assets[0], Path::new("content/posts/foo/index.md")),
+               Path::new("content/posts/foo/images/a.png"));
+  }
+
+  #[test]
+  fn test_relocate_missing() {
+    let (body, assets, warnings) = relocate("![gone](/images/gone.png)", None, &site(), &Placement::Bundle);
+    assert_eq!(body, "![gone](/images/gone.png)");
+    assert!(assets.is_empty());
+    assert_eq!(warnings, vec![String::from("reference to an asset that doesn't exist: /images/gone.png")]);
+  }
+}
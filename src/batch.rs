@@ -0,0 +1,270 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use crate::{read_post, write_post, ConvertError, Options, Post};
+use crate::check::{normalize, page_url};
+use crate::links::LinkIndex;
+use crate::filename::PostName;
+use crate::redirects::Redirect;
+use crate::section::{format_section, section_dirs};
+
+const POST_EXTENSIONS: [&str; 5] = ["md", "markdown", "mdown", "mkd", "mkdn"];
+
+/// Exit code when some posts failed to convert but others made it.
+pub const EXIT_SOME_FAILED: i32 = 1;
+/// Exit code when not a single post could be converted.
+pub const EXIT_ALL_FAILED: i32 = 2;
+
+pub struct Summary {
+  pub converted: Vec<PathBuf>,
+  pub failed: Vec<(PathBuf, ConvertError)>,
+  pub warnings: Vec<(PathBuf, String)>,
+  /// Names of the taxonomies that had terms in any converted post.
+  pub taxonomies: BTreeSet<String>,
+  pub redirects: Vec<Redirect>,
+}
+
+impl Summary {
+  pub fn total(&self) -> usize {
+    self.converted.len() + self.failed.len()
+  }
+
+  pub fn exit_code(&self) -> i32 {
+    if self.failed.is_empty() {
+      0
+    }
+    else if self.converted.is_empty() {
+      EXIT_ALL_FAILED
+    }
+    else {
+      EXIT_SOME_FAILED
+    }
+  }
+}
+
+impl std::fmt::Display for Summary {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    for (path, warning) in &self.warnings {
+      writeln!(f, "warning: {}: {}", path.display(), warning)?;
+    }
+    let mut by_kind: BTreeMap<&str, Vec<&(PathBuf, ConvertError)>> = BTreeMap::new();
+    for failure in &self.failed {
+      by_kind.entry(failure.1.kind()).or_default().push(failure);
+    }
+    for (kind, failures) in &by_kind {
+      writeln!(f, "{} ({} posts):", kind, failures.len())?;
+      for (path, e) in failures {
+        match e {
+          // already points at the file, and maybe at its sidecar rather than the post
+          ConvertError::Parse(_) => writeln!(f, "error: {}", e)?,
+          _ => writeln!(f, "error: {}: {}", path.display(), e)?,
+        }
+      }
+    }
+    write!(f, "{} converted, {} failed, {} total", self.converted.len(), self.failed.len(), self.total())
+  }
+}
+
+/// Convert every post under `input` into the same relative location under `output`,
+/// carrying on past posts that fail so that they can all be reported at the end.
+pub fn convert_dir(input: &Path, output: &Path, options: &Options) -> std::io::Result<Summary> {
+  let mut summary = Summary { converted: Vec::new(), failed: Vec::new(), warnings: Vec::new(), taxonomies: BTreeSet::new(), redirects: Vec::new() };
+  let posts = find_posts(input, &mut summary.failed)?;
+
+  // every post has to be read before any is written, to know where links between them go
+  let mut read = Vec::new();
+  for post in posts {
+    let relative = post.strip_prefix(input).unwrap();
+    match read_post(&post, &target_path(relative, output), relative.parent().unwrap(), options) {
+      Ok(read_post) => read.push(read_post),
+      Err(e) => summary.failed.push((post, e)),
+    }
+  }
+  let read = drop_clashes(read, options, &mut summary.failed);
+  let sections = section_dirs(read.iter().map(|post| post.output.as_path()), &options.content_root);
+  let mut links = LinkIndex::default();
+  for post in &read {
+    if let (Some(redirect), Ok(page)) = (&post.redirect, post.output.strip_prefix(&options.content_root)) {
+      links.insert(&redirect.from, page, &redirect.to);
+    }
+  }
+
+  for read_post in read {
+    let post = read_post.input.clone();
+    match write_post(read_post, Some(&links), options) {
+      Ok(conversion) => {
+        summary.taxonomies.extend(conversion.metadata.taxonomy_names());
+        summary.redirects.extend(conversion.redirect);
+        summary.warnings.extend(conversion.warnings.into_iter().map(|warning| (post.clone(), warning)));
+        summary.converted.push(post);
+      },
+      Err(e) => summary.failed.push((post, e)),
+    }
+  }
+  write_sections(&sections, options)?;
+  Ok(summary)
+}
+
+/// Leave out the posts that would overwrite an earlier post of `read`, or be served at its
+/// url, like `2019-01-01-foo.md` and `2022-01-01-foo.md` both becoming `foo.md`. They fail
+/// instead, and the earlier post, in path order, is converted.
+fn drop_clashes(read: Vec<Post>, options: &Options, failed: &mut Vec<(PathBuf, ConvertError)>) -> Vec<Post> {
+  let mut outputs: HashMap<PathBuf, PathBuf> = HashMap::new();
+  let mut urls: HashMap<String, PathBuf> = HashMap::new();
+  let mut kept = Vec::new();
+  for post in read {
+    let url = post.output.strip_prefix(&options.content_root).ok()
+      .map(|page| normalize(&page_url(page, post.metadata.slug.as_deref(), None)));
+    let taken = outputs.get(&post.output).or_else(|| url.as_ref().and_then(|url| urls.get(url)));
+    match taken {
+      Some(other) => failed.push((post.input.clone(), ConvertError::Clash(other.clone()))),
+      None => {
+        outputs.insert(post.output.clone(), post.input.clone());
+        if let Some(url) = url {
+          urls.insert(url, post.input.clone());
+        }
+        kept.push(post);
+      },
+    }
+  }
+  kept
+}
+
+/// Create `_index.md` in each of `dirs` that doesn't have one yet.
+fn write_sections(dirs: &BTreeSet<PathBuf>, options: &Options) -> std::io::Result<()> {
+  for dir in dirs {
+    let path = dir.join("_index.md");
+    if path.exists() {
+      continue;
+    }
+    let section = format_section(dir, &options.section).map_err(std::io::Error::other)?;
+    if options.dry_run {
+      println!("{}:\n{}", path.display(), section);
+    }
+    else {
+      std::fs::create_dir_all(dir)?;
+      std::fs::write(&path, section)?;
+    }
+  }
+  Ok(())
+}
+
+/// Names of the taxonomies that the posts under `input` have terms in, reading the posts
+/// as they would be converted without writing anything.
+pub fn scan_taxonomies(input: &Path, options: &Options, failed: &mut Vec<(PathBuf, ConvertError)>) -> std::io::Result<BTreeSet<String>> {
+  let mut taxonomies = BTreeSet::new();
+  for post in find_posts(input, failed)? {
+    let relative = post.strip_prefix(input).unwrap();
+    match read_post(&post, &target_path(relative, &options.content_root), relative.parent().unwrap(), options) {
+      Ok(read_post) => taxonomies.extend(read_post.metadata.taxonomy_names()),
+      Err(e) => failed.push((post, e)),
+    }
+  }
+  Ok(taxonomies)
+}
+
+/// Posts under `input` in a stable order, recording the directories that can't be read in `failed`.
+fn find_posts(input: &Path, failed: &mut Vec<(PathBuf, ConvertError)>) -> std::io::Result<Vec<PathBuf>> {
+  let mut posts = Vec::new();
+  for entry in std::fs::read_dir(input)? {
+    collect_posts(entry.map(|entry| entry.path()), &mut posts, failed);
+  }
+  posts.sort();
+  Ok(posts)
+}
+
+fn collect_posts(path: std::io::Result<PathBuf>, posts: &mut Vec<PathBuf>, failed: &mut Vec<(PathBuf, ConvertError)>) {
+  match path {
+    Ok(path) if path.is_dir() => match std::fs::read_dir(&path) {
+      Ok(entries) => entries.for_each(|entry| collect_posts(entry.map(|entry| entry.path()), posts, failed)),
+      Err(e) => failed.push((path, e.into())),
+    },
+    Ok(path) => {
+      if is_post(&path) {
+        posts.push(path);
+      }
+    },
+    Err(e) => failed.push((PathBuf::new(), e.into())),
+  }
+}
+
+fn is_post(path: &Path) -> bool {
+  match path.extension().and_then(|ext| ext.to_str()) {
+    Some(ext) => POST_EXTENSIONS.contains(&ext),
+    None => false,
+  }
+}
+
+/// Zola page for a post at `relative` to the directory being converted. The page drops
+/// the date prefix of the file name since zola takes it from `date`.
+fn target_path(relative: &Path, output: &Path) -> PathBuf {
+  let page = relative.with_file_name(PostName::from_path(relative).file_name(relative));
+  output.join(page.with_extension("md"))
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::{Path, PathBuf};
+  use std::collections::BTreeSet;
+  use super::{convert_dir, is_post, target_path, Summary, EXIT_SOME_FAILED, EXIT_ALL_FAILED};
+  use crate::{ConvertError, Options};
+
+  #[test]
+  fn test_is_post() {
+    assert!(is_post(Path::new("posts/2020-02-01-foo.md")));
+    assert!(is_post(Path::new("posts/2020-02-01-foo.markdown")));
+    assert!(!is_post(Path::new("images/foo.png")));
+    assert!(!is_post(Path::new("posts/README")));
+  }
+
+  #[test]
+  fn test_target_path() {
+    assert_eq!(target_path(Path::new("2020/foo.markdown"), Path::new("content/posts")),
+               PathBuf::from("content/posts/2020/foo.md"));
+  }
+
+  #[test]
+  fn test_target_path_strips_date() {
+    assert_eq!(target_path(Path::new("2020-02-01-foo.md"), Path::new("content/posts")),
+               PathBuf::from("content/posts/foo.md"));
+  }
+
+  fn io_error() -> ConvertError {
+    ConvertError::Io(std::io::Error::from(std::io::ErrorKind::PermissionDenied))
+  }
+
+  #[test]
+  fn test_summary() {
+    let mut summary = Summary { converted: Vec::new(), failed: Vec::new(), warnings: Vec::new(), taxonomies: BTreeSet::new(), redirects: Vec::new() };
+    assert_eq!(summary.exit_code(), 0);
+    summary.failed.push((PathBuf::from("posts/a.md"), io_error()));
+    summary.failed.push((PathBuf::from("posts/b.md"), io_error()));
+    assert_eq!(summary.exit_code(), EXIT_ALL_FAILED);
+    summary.converted.push(PathBuf::from("posts/c.md"));
+    assert_eq!(summary.exit_code(), EXIT_SOME_FAILED);
+    assert_eq!(summary.to_string(), "\
+      io error (2 posts):\n\
+      error: posts/a.md: io error: permission denied\n\
+      error: posts/b.md: io error: permission denied\n\
+      1 converted, 2 failed, 3 total");
+  }
+
+  #[test]
+  fn test_convert_dir_clash() {
+    let root = std::env::temp_dir().join(format!("hakyell2zola-clash-{}", std::process::id()));
+    let posts = root.join("posts");
+    let content = root.join("content");
+    std::fs::create_dir_all(&posts).unwrap();
+    std::fs::write(posts.join("2019-01-01-foo.md"), "---\ntitle: old\n---\nold\n").unwrap();
+    std::fs::write(posts.join("2022-01-01-foo.md"), "---\ntitle: new\n---\nnew\n").unwrap();
+    std::fs::write(posts.join("2022-02-01-bar.md"), "---\ntitle: bar\nslug: foo\n---\nbar\n").unwrap();
+    let options = Options { content_root: content.clone(), ..Options::default() };
+    let summary = convert_dir(&posts, &content.join("posts"), &options).unwrap();
+    assert_eq!(summary.converted, vec![posts.join("2019-01-01-foo.md")]);
+    let failed: Vec<&PathBuf> = summary.failed.iter().map(|(path, _)| path).collect();
+    assert_eq!(failed, vec![&posts.join("2022-01-01-foo.md"), &posts.join("2022-02-01-bar.md")]);
+    assert!(summary.failed.iter().all(|(_, e)| e.kind() == "duplicate page"));
+    assert_eq!(summary.exit_code(), EXIT_SOME_FAILED);
+    assert!(std::fs::read_to_string(content.join("posts/foo.md")).unwrap().contains("old"));
+    std::fs::remove_dir_all(&root).unwrap();
+  }
+}
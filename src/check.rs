@@ -0,0 +1,155 @@
+//! Make sure every page of the old hakyll site is still reachable after the migration.
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+pub struct Report {
+  /// Every url the old site served an html page at.
+  pub old_urls: Vec<String>,
+  /// Those of `old_urls` that neither a zola page nor an alias covers.
+  pub missing: Vec<String>,
+  /// Zola pages whose front matter couldn't be read.
+  pub unreadable: Vec<PathBuf>,
+}
+
+impl std::fmt::Display for Report {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    for path in &self.unreadable {
+      writeln!(f, "warning: {}: cannot read front matter", path.display())?;
+    }
+    for url in &self.missing {
+      writeln!(f, "missing: /{}", url)?;
+    }
+    write!(f, "{} of {} old urls have no destination", self.missing.len(), self.old_urls.len())
+  }
+}
+
+/// Compare the html hakyll generated into `site_output`, usually `_site`, with the pages
+/// and aliases under zola's `content` directory.
+pub fn check(site_output: &Path, content: &Path) -> std::io::Result<Report> {
+  let mut old_files = Vec::new();
+  collect_files(site_output, &mut old_files)?;
+  let mut old_urls: Vec<String> = old_files.iter()
+    .filter(|path| path.extension().is_some_and(|ext| ext == "html" || ext == "htm"))
+    .map(|path| url_of(path.strip_prefix(site_output).unwrap()))
+    .collect();
+  old_urls.sort();
+
+  let mut pages = Vec::new();
+  collect_files(content, &mut pages)?;
+  // zola renders the home page whether or not there is a content/_index.md
+  let mut destinations: BTreeSet<String> = vec![String::new()].into_iter().collect();
+  let mut unreadable = Vec::new();
+  for page in pages.iter().filter(|path| path.extension().is_some_and(|ext| ext == "md")) {
+    let front_matter = std::fs::read_to_string(page).ok().and_then(|text| front_matter(&text));
+    match front_matter {
+      Some(front_matter) => {
+        let slug = front_matter.get("slug").and_then(|slug| slug.as_str());
+        let path = front_matter.get("path").and_then(|path| path.as_str());
+        destinations.insert(normalize(&page_url(page.strip_prefix(content).unwrap(), slug, path)));
+        let aliases = front_matter.get("aliases").and_then(|aliases| aliases.as_array());
+        for alias in aliases.into_iter().flatten().filter_map(|alias| alias.as_str()) {
+          destinations.insert(normalize(alias));
+        }
+      },
+      None => unreadable.push(page.clone()),
+    }
+  }
+
+  let missing = old_urls.iter().filter(|url| !destinations.contains(&normalize(url))).cloned().collect();
+  Ok(Report { old_urls, missing, unreadable })
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+  for entry in std::fs::read_dir(dir)? {
+    let path = entry?.path();
+    if path.is_dir() {
+      collect_files(&path, files)?;
+    }
+    else {
+      files.push(path);
+    }
+  }
+  Ok(())
+}
+
+fn url_of(relative: &Path) -> String {
+  let components: Vec<String> = relative.components().map(|c| c.as_os_str().to_string_lossy().into_owned()).collect();
+  components.join("/")
+}
+
+/// The toml between the `+++` lines of a zola page.
+fn front_matter(text: &str) -> Option<toml::Value> {
+  let rest = text.trim_start_matches('\u{feff}').strip_prefix("+++")?;
+  let end = rest.find("\n+++")?;
+  rest[..end].parse().ok()
+}
+
+/// Where zola serves the page at `relative` to the content directory, given the `slug`
+/// and `path` of its front matter. Without a leading slash. The slug, or the file name
+/// standing for it, is slugified as zola does by default.
+pub fn page_url(relative: &Path, slug: Option<&str>, path: Option<&str>) -> String {
+  if let Some(path) = path {
+    return path.trim_start_matches('/').to_string();
+  }
+  let mut dir = relative.parent().map(url_of).unwrap_or_default();
+  let name = match relative.file_stem().and_then(|stem| stem.to_str()) {
+    Some("_index") => return dir,
+    Some("index") => {
+      // a page bundle, named after its directory
+      let name = dir.rsplit('/').next().unwrap_or("").to_string();
+      dir.truncate(dir.len() - name.len());
+      dir = dir.trim_end_matches('/').to_string();
+      name
+    },
+    Some(stem) => stem.to_string(),
+    None => String::new(),
+  };
+  let name = slug::slugify(slug.unwrap_or(&name));
+  if dir.is_empty() {
+    format!("{}/", name)
+  }
+  else {
+    format!("{}/{}/", dir, name)
+  }
+}
+
+/// `/posts/foo/index.html`, `posts/foo/` and `posts/foo` are all the same page.
+pub fn normalize(url: &str) -> String {
+  let url = url.trim_start_matches('/');
+  let url = url.strip_suffix("index.html").unwrap_or(url);
+  url.trim_end_matches('/').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::Path;
+  use super::{front_matter, page_url, normalize};
+
+  #[test]
+  fn test_page_url() {
+    assert_eq!(page_url(Path::new("posts/foo.md"), Some("bar"), None), "posts/bar/");
+    assert_eq!(page_url(Path::new("foo.md"), None, None), "foo/");
+    assert_eq!(page_url(Path::new("posts/foo.md"), Some("bar"), Some("/blog/foo")), "blog/foo");
+    assert_eq!(page_url(Path::new("posts/_index.md"), None, None), "posts");
+    assert_eq!(page_url(Path::new("posts/foo/index.md"), None, None), "posts/foo/");
+    assert_eq!(page_url(Path::new("posts/foo/index.md"), Some("bar"), None), "posts/bar/");
+    assert_eq!(page_url(Path::new("foo/index.md"), None, None), "foo/");
+    assert_eq!(page_url(Path::new("posts/MyPost.md"), None, None), "posts/mypost/");
+    assert_eq!(page_url(Path::new("posts/foo.md"), Some("Hello Wörld!"), None), "posts/hello-world/");
+  }
+
+  #[test]
+  fn test_front_matter() {
+    let page = front_matter("+++\ntitle = \"foo\"\nslug = \"bar\"\n+++\nbody").unwrap();
+    assert_eq!(page["slug"].as_str(), Some("bar"));
+    assert!(front_matter("---\ntitle: foo\n---\n").is_none());
+  }
+
+  #[test]
+  fn test_normalize() {
+    assert_eq!(normalize("/posts/foo/index.html"), "posts/foo");
+    assert_eq!(normalize("posts/foo/"), "posts/foo");
+    assert_eq!(normalize("/posts/foo.html"), "posts/foo.html");
+    assert_eq!(normalize("index.html"), "");
+  }
+}
@@ -0,0 +1,81 @@
+//! A starting `config.toml` for zola, from the `Configuration` and `FeedConfiguration` of
+//! `site.hs` and the taxonomies the posts use.
+use std::collections::BTreeSet;
+use serde::Serialize;
+use crate::metadata::taxonomies_config;
+use crate::site::Site;
+
+/// Where hakyll writes the site unless `destinationDirectory` says otherwise.
+const HAKYLL_DESTINATION: &str = "_site";
+
+#[derive(Serialize)]
+struct Config<'a> {
+  base_url: &'a str,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  title: Option<&'a str>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  description: Option<&'a str>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  author: Option<&'a str>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  output_dir: Option<&'a str>,
+  generate_feed: bool,
+}
+
+pub fn format_config(site: &Site, taxonomies: &BTreeSet<String>) -> Result<String, toml::ser::Error> {
+  let base_url = site.field("feedRoot");
+  let config = Config {
+    base_url: base_url.unwrap_or("https://example.com"),
+    title: site.field("feedTitle"),
+    description: site.field("feedDescription"),
+    author: site.field("feedAuthorName"),
+    // keep deploying from where hakyll used to write to
+    output_dir: site.field("destinationDirectory").filter(|dir| *dir != HAKYLL_DESTINATION),
+    generate_feed: site.fields.keys().any(|name| name.starts_with("feed")),
+  };
+  let mut buf = String::new();
+  if base_url.is_none() {
+    buf.push_str("# site.hs has no feedRoot to take the url of the site from\n");
+  }
+  config.serialize(toml::Serializer::new(&mut buf).pretty_string(true).pretty_string_literal(false))?;
+  if !taxonomies.is_empty() {
+    buf.push('\n');
+    buf.push_str(&taxonomies_config(taxonomies));
+  }
+  Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::BTreeSet;
+  use super::format_config;
+  use crate::site::Site;
+
+  #[test]
+  fn test_format_config() {
+    let site = Site::parse(r#"
+config = defaultConfiguration { destinationDirectory = "docs" }
+feedConfig = FeedConfiguration { feedTitle = "blog", feedRoot = "https://example.org" }
+"#);
+    let taxonomies: BTreeSet<String> = vec![String::from("tags")].into_iter().collect();
+    let config = format_config(&site, &taxonomies).unwrap();
+    assert_eq!(config, "\
+      base_url = \"https://example.org\"\n\
+      title = \"blog\"\n\
+      output_dir = \"docs\"\n\
+      generate_feed = true\n\
+      \n\
+      [[taxonomies]]\n\
+      name = \"tags\"\n");
+    assert!(config.parse::<toml::Value>().is_ok());
+  }
+
+  #[test]
+  fn test_format_config_defaults() {
+    let config = format_config(&Site::parse("main = hakyll $ return ()"), &BTreeSet::new()).unwrap();
+    assert_eq!(config, "\
+      # site.hs has no feedRoot to take the url of the site from\n\
+      base_url = \"https://example.com\"\n\
+      generate_feed = false\n");
+  }
+}
@@ -0,0 +1,134 @@
+//! What to make of the fields hakyll contexts add, which zola has no counterpart for. Each
+//! becomes either a tera expression in the templates or a value under `[extra]` of every page.
+//!
+//! The table is read from a toml file keyed by field name:
+//!
+//! ```toml
+//! [date]
+//! tera = 'page.date | date(format="%B %e, %Y")'
+//!
+//! [teaser]
+//! tera = "page.summary"
+//! safe = true
+//!
+//! [author]
+//! extra = "Jane"
+//! ```
+//!
+//! Expressions are written for `page`, which is replaced by the loop variable within `$for$`.
+use std::collections::BTreeMap;
+use serde::Deserialize;
+use crate::site::{ContextField, Site};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Mapping {
+  Tera {
+    expr: String,
+    /// Whether the expression holds html to output as it is.
+    safe: bool,
+  },
+  /// Set under `[extra]` of every page that doesn't set the field itself.
+  Extra(toml::Value),
+}
+
+#[derive(Debug, Default)]
+pub struct FieldMap(BTreeMap<String, Mapping>);
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Entry {
+  tera: Option<String>,
+  #[serde(default)]
+  safe: bool,
+  extra: Option<toml::Value>,
+}
+
+impl FieldMap {
+  pub fn parse(source: &str) -> Result<FieldMap, String> {
+    let entries: BTreeMap<String, Entry> = toml::from_str(source).map_err(|e| e.to_string())?;
+    let mut map = FieldMap::default();
+    for (name, entry) in entries {
+      let mapping = match (entry.tera, entry.extra) {
+        (Some(expr), None) => Mapping::Tera { expr, safe: entry.safe },
+        (None, Some(value)) => Mapping::Extra(value),
+        _ => return Err(format!("field {:?} needs either tera or extra", name)),
+      };
+      map.0.insert(name, mapping);
+    }
+    Ok(map)
+  }
+
+  /// The mappings for the fields the contexts of `site` define.
+  pub fn from_site(site: &Site) -> FieldMap {
+    let mut map = FieldMap::default();
+    for field in &site.context_fields {
+      let (name, mapping) = match field {
+        ContextField::Date { name, format } => {
+          (name, Mapping::Tera { expr: format!("page.date | date(format={:?})", format), safe: false })
+        },
+        ContextField::Teaser { name, .. } => (name, Mapping::Tera { expr: String::from("page.summary"), safe: true }),
+        ContextField::Tags { name } => {
+          (name, Mapping::Tera { expr: format!("page.taxonomies.{} | default(value=[]) | join(sep=\", \")", name), safe: false })
+        },
+        ContextField::Const { name, value } => (name, Mapping::Extra(toml::Value::String(value.clone()))),
+      };
+      map.0.insert(name.clone(), mapping);
+    }
+    map
+  }
+
+  /// Add the mappings of `other`, which win over those already here.
+  pub fn extend(&mut self, other: FieldMap) {
+    self.0.extend(other.0);
+  }
+
+  pub fn get(&self, name: &str) -> Option<&Mapping> {
+    self.0.get(name)
+  }
+
+  /// Values to set under `[extra]` of every page.
+  fn extra(&self) -> impl Iterator<Item = (&str, &toml::Value)> {
+    self.0.iter().filter_map(|(name, mapping)| match mapping {
+      Mapping::Extra(value) => Some((name.as_str(), value)),
+      Mapping::Tera { .. } => None,
+    })
+  }
+
+  /// Set the `extra` values in the front matter `extra` of a post, unless the post has its own.
+  pub fn materialize(&self, extra: &mut BTreeMap<String, serde_yaml::Value>) {
+    for (name, value) in self.extra() {
+      if let (false, Ok(value)) = (extra.contains_key(name), serde_yaml::to_value(value)) {
+        extra.insert(name.to_string(), value);
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::BTreeMap;
+  use super::{FieldMap, Mapping};
+  use crate::site::Site;
+
+  #[test]
+  fn test_parse() {
+    let map = FieldMap::parse("[teaser]\ntera = \"page.summary\"\nsafe = true\n[author]\nextra = \"Jane\"\n").unwrap();
+    assert_eq!(map.get("teaser"), Some(&Mapping::Tera { expr: String::from("page.summary"), safe: true }));
+    assert_eq!(map.extra().collect::<Vec<_>>(), vec![("author", &toml::Value::String(String::from("Jane")))]);
+    assert!(FieldMap::parse("[author]\n").is_err());
+    assert!(FieldMap::parse("[author]\nextra = 1\ntera = \"x\"\n").is_err());
+  }
+
+  #[test]
+  fn test_from_site() {
+    let site = Site::parse("ctx = dateField \"date\" \"%Y-%m-%d\" <> tagsField \"tags\" tags <> constField \"author\" \"Jane\"");
+    let mut map = FieldMap::from_site(&site);
+    assert_eq!(map.get("date"), Some(&Mapping::Tera { expr: String::from("page.date | date(format=\"%Y-%m-%d\")"), safe: false }));
+    assert_eq!(map.get("tags"), Some(&Mapping::Tera { expr: String::from("page.taxonomies.tags | default(value=[]) | join(sep=\", \")"), safe: false }));
+    let mut extra = BTreeMap::new();
+    map.materialize(&mut extra);
+    assert_eq!(extra.get("author"), Some(&serde_yaml::Value::String(String::from("Jane"))));
+    map.extend(FieldMap::parse("[author]\ntera = \"config.extra.author\"\n").unwrap());
+    assert_eq!(map.extra().count(), 0);
+  }
+}
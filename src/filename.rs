@@ -0,0 +1,69 @@
+use std::path::Path;
+use chrono::NaiveDate;
+
+/// What hakyll's `YYYY-MM-DD-title.md` naming convention says about a post.
+#[derive(Debug, Eq, PartialEq)]
+pub struct PostName {
+  pub date: Option<String>,
+  pub slug: String,
+}
+
+impl PostName {
+  pub fn from_path(path: &Path) -> PostName {
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+    match split_date(stem) {
+      Some((date, slug)) => PostName { date: Some(date.to_string()), slug: slug.to_string() },
+      None => PostName { date: None, slug: stem.to_string() },
+    }
+  }
+
+  /// File name of the zola page, i.e. the slug with the original extension.
+  pub fn file_name(&self, path: &Path) -> String {
+    match path.extension().and_then(|ext| ext.to_str()) {
+      Some(ext) => format!("{}.{}", self.slug, ext),
+      None => self.slug.clone(),
+    }
+  }
+}
+
+fn split_date(stem: &str) -> Option<(&str, &str)> {
+  let date = stem.get(0..10)?;
+  let slug = stem.get(10..)?.strip_prefix('-')?;
+  if slug.is_empty() || NaiveDate::parse_from_str(date, "%Y-%m-%d").is_err() {
+    return None;
+  }
+  Some((date, slug))
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::Path;
+  use super::PostName;
+
+  #[test]
+  fn test_from_path_with_date() {
+    let path = Path::new("posts/2020-02-01-bigdata-ingestion.md");
+    let name = PostName::from_path(path);
+    assert_eq!(name, PostName {
+      date: Some(String::from("2020-02-01")),
+      slug: String::from("bigdata-ingestion"),
+    });
+    assert_eq!(name.file_name(path), "bigdata-ingestion.md");
+  }
+
+  #[test]
+  fn test_from_path_without_date() {
+    assert_eq!(PostName::from_path(Path::new("posts/about.md")), PostName {
+      date: None,
+      slug: String::from("about"),
+    });
+    assert_eq!(PostName::from_path(Path::new("posts/2020-13-01-foo.md")), PostName {
+      date: None,
+      slug: String::from("2020-13-01-foo"),
+    });
+    assert_eq!(PostName::from_path(Path::new("posts/2020-02-01.md")), PostName {
+      date: None,
+      slug: String::from("2020-02-01"),
+    });
+  }
+}
@@ -0,0 +1,135 @@
+//! Links between posts, moved from their hakyll urls to zola's `@/` internal links so that
+//! zola checks them and they follow the pages wherever they end up.
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use regex::{Captures, Regex};
+use crate::check::normalize;
+use crate::markdown::{dir_of, is_external, map_text_lines, resolve_dots, url_patterns};
+
+#[derive(Default)]
+pub struct LinkIndex {
+  /// Normalized old url to the zola page relative to the content directory, and its new url.
+  pages: HashMap<String, (String, String)>,
+  /// Directories the old urls were in, as `page_dir` gives them, to tell links to missing
+  /// posts from links to pages that were never posts, like the archive.
+  dirs: HashSet<String>,
+}
+
+enum Resolution {
+  /// The link to put in place of the old one.
+  Page(String),
+  Missing,
+}
+
+impl LinkIndex {
+  /// `new_url` is for links in raw html, where zola doesn't resolve internal links.
+  pub fn insert(&mut self, old_url: &str, page: &Path, new_url: &str) {
+    self.dirs.insert(page_dir(old_url));
+    let old_url = normalize(old_url);
+    let components: Vec<String> = page.components().map(|c| c.as_os_str().to_string_lossy().into_owned()).collect();
+    self.pages.insert(old_url, (components.join("/"), new_url.to_string()));
+  }
+
+  /// Rewrite the links in `body` of the post that used to be at `old_url`, which relative
+  /// links are resolved against. Returns warnings for links to posts that aren't there.
+  pub fn rewrite(&self, body: &str, old_url: Option<&str>) -> (String, Vec<String>) {
+    let [inline, reference, href] = url_patterns();
+    let mut warnings = Vec::new();
+    let rewritten = map_text_lines(body, |line| {
+      let line = self.replace(inline, line, false, old_url, &mut warnings);
+      let line = self.replace(reference, &line, false, old_url, &mut warnings);
+      self.replace(href, &line, true, old_url, &mut warnings).into_owned()
+    });
+    (rewritten, warnings)
+  }
+
+  /// Replace the urls `pattern` captures as its second group.
+  fn replace<'t>(&self, pattern: &Regex, line: &'t str, html: bool, old_url: Option<&str>, warnings: &mut Vec<String>) -> Cow<'t, str> {
+    pattern.replace_all(line, |caps: &Captures| {
+      let url = &caps[2];
+      match self.resolve(url, html, old_url) {
+        Some(Resolution::Page(link)) => format!("{}{}{}", &caps[1], link, &caps[3]),
+        Some(Resolution::Missing) => {
+          warnings.push(format!("link to a post that doesn't exist: {}", url));
+          caps[0].to_string()
+        },
+        None => caps[0].to_string(),
+      }
+    })
+  }
+
+  fn resolve(&self, url: &str, html: bool, old_url: Option<&str>) -> Option<Resolution> {
+    if is_external(url) {
+      return None;
+    }
+    let split = url.find(['#', '?']).unwrap_or(url.len());
+    let (path, fragment) = url.split_at(split);
+    let path = match path.strip_prefix('/') {
+      Some(absolute) => absolute.to_string(),
+      // as the browser does, so `a/index.html` and `a/` are the base of `../b/` and not `a`
+      None => format!("{}{}", dir_of(old_url?.trim_start_matches('/')), path),
+    };
+    let path = resolve_dots(&path)?;
+    let is_page = path.ends_with(".html") || path.ends_with('/');
+    match self.pages.get(&normalize(&path)) {
+      Some((_, new_url)) if html => Some(Resolution::Page(format!("{}{}", new_url, fragment))),
+      Some((page, _)) => Some(Resolution::Page(format!("@/{}{}", page, fragment))),
+      None if is_page && self.dirs.contains(&page_dir(&path)) => Some(Resolution::Missing),
+      None => None,
+    }
+  }
+}
+
+/// The directory the page at `url` is in: `posts/` for both `/posts/foo.html` and
+/// `/posts/foo/index.html`.
+fn page_dir(url: &str) -> String {
+  dir_of(&normalize(url)).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::Path;
+  use super::LinkIndex;
+
+  fn index() -> LinkIndex {
+    let mut index = LinkIndex::default();
+    index.insert("/posts/2019-01-01-foo.html", Path::new("posts/foo.md"), "/posts/foo/");
+    index.insert("/posts/2020-02-01-bar.html", Path::new("posts/bar.md"), "/posts/bar/");
+    index
+  }
+
+  #[test]
+  fn test_rewrite() {
+    let body = "\
+      see [foo](/posts/2019-01-01-foo.html#usage) and [bar](2020-02-01-bar.html \"bar\")\n\
+      or <a href=\"../posts/2019-01-01-foo.html\">foo</a>, [about](/about.html), [web](https://example.com/posts/x.html)\n\
+      [ref]: /posts/2020-02-01-bar.html\n";
+    let (rewritten, warnings) = index().rewrite(body, Some("/posts/2020-02-01-bar.html"));
+    assert_eq!(rewritten, "\
+      see [foo](@/posts/foo.md#usage) and [bar](@/posts/bar.md \"bar\")\n\
+      or <a href=\"/posts/foo/\">foo</a>, [about](/about.html), [web](https://example.com/posts/x.html)\n\
+      [ref]: @/posts/bar.md\n");
+    assert!(warnings.is_empty());
+  }
+
+  #[test]
+  fn test_rewrite_missing() {
+    let body = "[gone](/posts/2018-01-01-gone.html)\n```\n[code](/posts/2019-01-01-foo.html)\n```\n";
+    let (rewritten, warnings) = index().rewrite(body, None);
+    assert_eq!(rewritten, body);
+    assert_eq!(warnings, vec![String::from("link to a post that doesn't exist: /posts/2018-01-01-gone.html")]);
+  }
+
+  #[test]
+  fn test_rewrite_directory_urls() {
+    let mut index = LinkIndex::default();
+    index.insert("/posts/2020/02/01/a/index.html", Path::new("posts/a.md"), "/posts/a/");
+    index.insert("/posts/2020/02/01/b2/index.html", Path::new("posts/b2.md"), "/posts/b2/");
+    index.insert("/posts/2020/03/01/b/index.html", Path::new("posts/b.md"), "/posts/b/");
+    let body = "[b2](../b2/) [b](../../../03/01/b/) [gone](../gone/) [broken](../03/01/b/)\n";
+    let (rewritten, warnings) = index.rewrite(body, Some("/posts/2020/02/01/a/index.html"));
+    assert_eq!(rewritten, "[b2](@/posts/b2.md) [b](@/posts/b.md) [gone](../gone/) [broken](../03/01/b/)\n");
+    assert_eq!(warnings, vec![String::from("link to a post that doesn't exist: ../gone/")]);
+  }
+}
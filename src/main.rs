@@ -1,6 +1,24 @@
-use serde::{Serialize, Deserialize};
-use std::path::{Path,PathBuf};
-use clap::{Arg, App};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use clap::{Arg, App, AppSettings};
+
+mod alias;
+mod assets;
+mod batch;
+mod check;
+mod config;
+mod fields;
+mod filename;
+mod links;
+mod markdown;
+mod metadata;
+mod pandoc;
+mod redirects;
+mod section;
+mod site;
+mod template;
+
+use metadata::Metadata;
 
 fn main() -> std::io::Result<()> {
 
@@ -8,115 +26,724 @@ fn main() -> std::io::Result<()> {
                   .version("1.0")
                   .author("Yutaka Imamura <ilyaletre@gmail.com>")
                   .about("convert hakyll doc to zola doc")
-                  .arg(Arg::with_name("input").long("input").short('i').required(true).takes_value(true))
-                  .arg(Arg::with_name("output").long("output").short('o').required(true).takes_value(true))
-                  .arg(Arg::with_name("alias").long("alias").short('a').required(true).takes_value(true))
+                  .setting(AppSettings::SubcommandsNegateReqs)
+                  .arg(Arg::with_name("input").long("input").short('i').required(true).takes_value(true)
+                       .about("hakyll post, or a directory of posts to convert in batch"))
+                  .arg(Arg::with_name("output").long("output").short('o').required(true).takes_value(true)
+                       .about("zola page, or the content directory when input is a directory"))
+                  .arg(Arg::with_name("alias").long("alias").short('a').takes_value(true)
+                       .required_unless_one(&["alias-template", "site"]).conflicts_with("alias-template")
+                       .about("directory the posts were served from with hakyll's setExtension \"html\" route"))
+                  .arg(Arg::with_name("alias-template").long("alias-template").takes_value(true)
+                       .about("old url of each post with {year}, {month}, {day}, {slug}, {stem} and {dir} filled in"))
+                  .arg(Arg::with_name("site").long("site").takes_value(true)
+                       .about("hakyll's site.hs, to take the old url of each post from its routes"))
+                  .arg(Arg::with_name("taxonomy").long("taxonomy").short('t').takes_value(true)
+                       .multiple_occurrences(true).number_of_values(1)
+                       .about("front matter key to convert into a zola taxonomy besides tags, e.g. categories"))
+                  .arg(Arg::with_name("dry-run").long("dry-run")
+                       .about("print where each post would go and a diff of the conversion without writing anything"))
+                  .arg(Arg::with_name("redirects").long("redirects").takes_value(true)
+                       .about("also write a server redirect file from each old url to its zola page"))
+                  .arg(Arg::with_name("redirect-format").long("redirect-format").takes_value(true)
+                       .possible_values(&redirects::FORMATS).default_value("netlify"))
+                  .arg(Arg::with_name("content-root").long("content-root").takes_value(true)
+                       .about("zola's content directory, which page urls are relative to; defaults to the content directory the output is in, or else the output directory"))
+                  .arg(Arg::with_name("allow-missing-header").long("allow-missing-header")
+                       .about("convert posts without front matter, taking the title from the first heading"))
+                  .arg(Arg::with_name("assets").long("assets").takes_value(true).possible_values(&assets::PLACEMENTS)
+                       .about("copy the images and files posts refer to into zola's static directory or next to each post"))
+                  .arg(Arg::with_name("static-dir").long("static-dir").takes_value(true)
+                       .about("zola's static directory for --assets static; defaults to static next to the content directory"))
+                  .arg(Arg::with_name("bundle").long("bundle")
+                       .about("write each post as a page bundle, <slug>/index.md, with its assets next to it"))
+                  .arg(Arg::with_name("fields").long("fields").takes_value(true)
+                       .about("toml table of hakyll context fields, those with extra values set under [extra] of every post"))
+                  .arg(Arg::with_name("paginate-by").long("paginate-by").takes_value(true)
+                       .about("number of posts per page of the _index.md generated for each directory"))
+                  .arg(Arg::with_name("section-template").long("section-template").takes_value(true)
+                       .about("template of the _index.md generated for each directory"))
+                  .subcommand(App::new("check")
+                              .about("check that every page of the old site has a zola page or alias to go to")
+                              .arg(Arg::with_name("site-output").long("site-output").required(true).takes_value(true)
+                                   .about("hakyll's build output, usually _site"))
+                              .arg(Arg::with_name("content").long("content").required(true).takes_value(true)
+                                   .about("zola's content directory after the conversion")))
+                  .subcommand(App::new("config")
+                              .about("print a starting config.toml from site.hs and the taxonomies the posts use")
+                              .arg(Arg::with_name("site").long("site").required(true).takes_value(true)
+                                   .about("hakyll's site.hs"))
+                              .arg(Arg::with_name("input").long("input").short('i').takes_value(true)
+                                   .about("directory of the posts to collect taxonomies from"))
+                              .arg(Arg::with_name("taxonomy").long("taxonomy").short('t').takes_value(true)
+                                   .multiple_occurrences(true).number_of_values(1)
+                                   .about("front matter key to treat as a taxonomy, in addition to tags"))
+                              .arg(Arg::with_name("allow-missing-header").long("allow-missing-header")
+                                   .about("read posts without front matter too")))
+                  .subcommand(App::new("template")
+                              .about("translate hakyll templates to tera templates for zola")
+                              .arg(Arg::with_name("input").long("input").short('i').required(true).takes_value(true)
+                                   .about("hakyll template, or a directory of them like templates"))
+                              .arg(Arg::with_name("output").long("output").short('o').required(true).takes_value(true)
+                                   .about("tera template, or zola's templates directory when input is a directory"))
+                              .arg(Arg::with_name("site").long("site").takes_value(true)
+                                   .about("hakyll's site.hs, to take the fields of its contexts from"))
+                              .arg(Arg::with_name("fields").long("fields").takes_value(true)
+                                   .about("toml table of what hakyll context fields become in tera")))
                   .get_matches();
 
-  let mut alias_path: PathBuf = PathBuf::from(matches.value_of("alias").unwrap());
+  if let ("check", Some(matches)) = matches.subcommand() {
+    let site_output = Path::new(matches.value_of("site-output").unwrap());
+    let content = Path::new(matches.value_of("content").unwrap());
+    let report = check::check(site_output, content)?;
+    println!("{}", report);
+    std::process::exit(if report.missing.is_empty() { 0 } else { 1 });
+  }
+
+  if let ("config", Some(matches)) = matches.subcommand() {
+    let site_path = Path::new(matches.value_of("site").unwrap());
+    let site = site::Site::parse(&std::fs::read_to_string(site_path)?);
+    let mut taxonomies = match matches.value_of("input") {
+      Some(input) => {
+        let options = Options {
+          taxonomies: matches.values_of("taxonomy").map(|keys| keys.map(String::from).collect()).unwrap_or_default(),
+          allow_missing_header: matches.is_present("allow-missing-header"),
+          ..Options::default()
+        };
+        let mut failed = Vec::new();
+        let taxonomies = batch::scan_taxonomies(Path::new(input), &options, &mut failed)?;
+        for (path, e) in failed {
+          eprintln!("warning: {}: {}", path.display(), e);
+        }
+        taxonomies
+      },
+      None => BTreeSet::new(),
+    };
+    if site.categories {
+      // every post is filed under the directory it is in
+      taxonomies.insert(String::from(metadata::CATEGORIES));
+    }
+    print!("{}", config::format_config(&site, &taxonomies).map_err(std::io::Error::other)?);
+    return Ok(());
+  }
+
+  if let ("template", Some(matches)) = matches.subcommand() {
+    let input = Path::new(matches.value_of("input").unwrap());
+    let output = Path::new(matches.value_of("output").unwrap());
+    let site = match matches.value_of("site") {
+      Some(path) => Some(site::Site::parse(&std::fs::read_to_string(path)?)),
+      None => None,
+    };
+    let fields = load_fields(site.as_ref(), matches.value_of("fields"))?;
+    for (path, warning) in template::translate_files(input, output, &fields)? {
+      println!("warning: {}: {}", path.display(), warning);
+    }
+    return Ok(());
+  }
+
+  let alias = match (matches.value_of("alias-template"), matches.value_of("alias")) {
+    (Some(template), _) => Some(alias::AliasTemplate::parse(template).map_err(std::io::Error::other)?),
+    (None, Some(dir)) => Some(alias::AliasTemplate::from_dir(Path::new(dir))),
+    (None, None) => None,
+  };
+  let (site, site_root) = match matches.value_of("site") {
+    Some(path) => {
+      let path = Path::new(path);
+      let site = site::Site::parse(&std::fs::read_to_string(path)?);
+      for warning in &site.warnings {
+        println!("warning: {}", warning);
+      }
+      let root = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or_else(|| Path::new("."));
+      (Some(site), root.canonicalize()?)
+    },
+    None => (None, Path::new(".").canonicalize()?),
+  };
+  let fields = load_fields(site.as_ref(), matches.value_of("fields"))?;
+  let teaser_separators = match &site {
+    Some(site) => site.teaser_separators(),
+    None => vec![String::from(site::TEASER_SEPARATOR)],
+  };
   let input: &Path = Path::new(matches.value_of("input").unwrap());
   let output: &Path = Path::new(matches.value_of("output").unwrap());
+  let output_dir = if input.is_dir() { output } else { output.parent().unwrap() };
+  let content_root = match (matches.value_of("content-root"), content_dir_of(output_dir)) {
+    (Some(dir), _) => PathBuf::from(dir),
+    (None, Some(dir)) => dir.to_path_buf(),
+    // redirects have to go where zola serves the pages, which the root decides
+    (None, None) if matches.is_present("redirects") => {
+      return Err(std::io::Error::other(format!("no content directory above {}, give it with --content-root", output.display())));
+    },
+    (None, None) => output_dir.to_path_buf(),
+  };
+  let bundle = matches.is_present("bundle");
+  let assets = match matches.value_of("assets") {
+    Some("bundle") => Some(assets::Placement::Bundle),
+    None if bundle => Some(assets::Placement::Bundle),
+    Some(_) => Some(assets::Placement::Static(match matches.value_of("static-dir") {
+      Some(dir) => PathBuf::from(dir),
+      None => content_root.parent().unwrap_or_else(|| Path::new("")).join("static"),
+    })),
+    None => None,
+  };
+  let section = section::SectionOptions {
+    paginate_by: match matches.value_of("paginate-by") {
+      Some(n) => Some(n.parse().map_err(|e| std::io::Error::other(format!("--paginate-by {:?}: {}", n, e)))?),
+      None => None,
+    },
+    template: matches.value_of("section-template").map(String::from),
+  };
+  let redirect_format: redirects::Format = matches.value_of("redirect-format").unwrap().parse().map_err(std::io::Error::other)?;
+  let redirects_path = matches.value_of("redirects").map(Path::new);
+  let options = Options {
+    taxonomies: matches.values_of("taxonomy").map(|keys| keys.map(String::from).collect()).unwrap_or_default(),
+    allow_missing_header: matches.is_present("allow-missing-header"),
+    dry_run: matches.is_present("dry-run"),
+    alias,
+    site,
+    site_root,
+    content_root,
+    assets,
+    bundle,
+    section,
+    fields,
+    teaser_separators,
+  };
+
+  if input.is_dir() {
+    let summary = batch::convert_dir(input, output, &options)?;
+    println!("{}", summary);
+    print_taxonomies(&summary.taxonomies);
+    if let Some(path) = redirects_path {
+      write_redirects(path, &summary.redirects, redirect_format, &options)?;
+    }
+    std::process::exit(summary.exit_code());
+  }
+  else {
+    match convert_file(input, output, Path::new(""), &options) {
+      Ok(conversion) => {
+        for warning in &conversion.warnings {
+          println!("warning: {}", warning);
+        }
+        print_taxonomies(&conversion.metadata.taxonomy_names());
+        match redirects_path {
+          Some(path) => write_redirects(path, conversion.redirect.as_slice(), redirect_format, &options),
+          None => Ok(()),
+        }
+      },
+      Err(e) => {
+        println!("{}", e);
+        Err(std::io::Error::other(e.to_string()))
+      },
+    }
+  }
+}
+
+/// What the user chose on the command line about how posts are converted.
+#[derive(Default)]
+struct Options {
+  /// Front matter keys to treat as taxonomies in addition to `tags`.
+  taxonomies: Vec<String>,
+  /// Make up metadata for posts without front matter instead of failing on them.
+  allow_missing_header: bool,
+  /// Show the conversion instead of writing it out.
+  dry_run: bool,
+  /// Where each post used to be served, to keep the old url working. Used for posts
+  /// the routes of `site` don't tell about.
+  alias: Option<alias::AliasTemplate>,
+  site: Option<site::Site>,
+  /// Directory of `site.hs`, or the current directory without one, which hakyll
+  /// identifiers and absolute urls are relative to.
+  site_root: PathBuf,
+  /// Zola's `content` directory, which the urls of the converted pages follow.
+  content_root: PathBuf,
+  /// Where to copy the images and files the posts refer to, if anywhere.
+  assets: Option<assets::Placement>,
+  /// Write every post as `<slug>/index.md` rather than `<slug>.md`.
+  bundle: bool,
+  /// How to set up the sections created for the directories of a batch conversion.
+  section: section::SectionOptions,
+  /// What the fields of hakyll contexts become, of which only those set as extra matter here.
+  fields: fields::FieldMap,
+  /// What hakyll's teaser fields cut posts at, to be replaced with zola's summary marker.
+  teaser_separators: Vec<String>,
+}
+
+/// A post that made it into zola, along with anything about it the user should double check.
+struct Conversion {
+  metadata: Metadata,
+  /// From the old url to the zola page, when the old url is known.
+  redirect: Option<redirects::Redirect>,
+  warnings: Vec<String>,
+}
+
+fn write_redirects(path: &Path, redirects: &[redirects::Redirect], format: redirects::Format, options: &Options) -> std::io::Result<()> {
+  let rendered = redirects::render(redirects, format);
+  if options.dry_run {
+    println!("{}:\n{}", path.display(), rendered);
+    Ok(())
+  }
+  else {
+    std::fs::write(path, rendered)
+  }
+}
+
+fn print_taxonomies(names: &BTreeSet<String>) {
+  if !names.is_empty() {
+    println!("add the following to config.toml:\n{}", metadata::taxonomies_config(names));
+  }
+}
+
+/// The fields the contexts of `site` define, overridden by the table at `path`.
+fn load_fields(site: Option<&site::Site>, path: Option<&str>) -> std::io::Result<fields::FieldMap> {
+  let mut fields = site.map(fields::FieldMap::from_site).unwrap_or_default();
+  if let Some(path) = path {
+    let table = fields::FieldMap::parse(&std::fs::read_to_string(path)?)
+      .map_err(|e| std::io::Error::other(format!("{}: {}", path, e)))?;
+    fields.extend(table);
+  }
+  Ok(fields)
+}
+
+/// Path of hakyll's `foo.md.metadata` file next to `foo.md`.
+fn sidecar_path(input: &Path) -> PathBuf {
+  let mut path = input.as_os_str().to_owned();
+  path.push(".metadata");
+  PathBuf::from(path)
+}
+
+/// The nearest directory named `content` from `dir` up, which zola's content directory is
+/// usually called.
+fn content_dir_of(dir: &Path) -> Option<&Path> {
+  dir.ancestors().find(|dir| dir.file_name().is_some_and(|name| name == "content"))
+}
+
+/// `<slug>/index.md` next to `output`, the page bundle zola looks for colocated assets in.
+fn bundle_path(output: &Path, slug: &str) -> PathBuf {
+  output.with_file_name(slug).join("index.md")
+}
+
+/// Content of the sidecar metadata file at `path`, if there is one.
+fn read_sidecar(path: &Path) -> std::io::Result<Option<String>> {
+  match std::fs::read_to_string(path) {
+    Ok(content) => Ok(Some(content)),
+    Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+    Err(e) => Err(e),
+  }
+}
 
+/// Hakyll identifier of `input`, i.e. its path relative to the site with `/` separators.
+fn identifier(input: &Path, site_root: &Path) -> Option<String> {
+  let input = input.canonicalize().ok()?;
+  let relative = input.strip_prefix(site_root).ok()?;
+  let components: Vec<String> = relative.components().map(|c| c.as_os_str().to_string_lossy().into_owned()).collect();
+  Some(components.join("/"))
+}
+
+/// Convert a post on its own, with no other posts to rewrite links to.
+fn convert_file(input: &Path, output: &Path, dir: &Path, options: &Options) -> ConvertResult<Conversion> {
+  write_post(read_post(input, output, dir, options)?, None, options)
+}
+
+/// A post read and converted in memory, not yet written out.
+struct Post {
+  input: PathBuf,
+  output: PathBuf,
+  content: String,
+  /// What follows the front matter, with the references to assets already moved.
+  body: String,
+  metadata: Metadata,
+  redirect: Option<redirects::Redirect>,
+  assets: Vec<assets::Asset>,
+  warnings: Vec<String>,
+}
+
+/// `dir` is where `input` is relative to the directory being converted, for the alias.
+fn read_post(input: &Path, output: &Path, dir: &Path, options: &Options) -> ConvertResult<Post> {
   let content = std::fs::read_to_string(input)?;
   let mut stream = Stream::new(&content);
-  match stream.read_header() {
-    Ok(mut metadata) => {
-      alias_path.push(input.with_extension("html").file_name().unwrap());
-      metadata.alias = Some(alias_path.as_os_str().to_str().unwrap().to_string());
-      let mut buf = String::new();
-      buf.push_str(&metadata.format_header());
-      buf.push_str(stream.current());
-      std::fs::create_dir_all(output.parent().unwrap())?;
-      std::fs::write(output, buf)?;
-      Ok(())
+  let mut warnings = Vec::new();
+  let post_name = filename::PostName::from_path(input);
+  let at_input = |e| SourceError::new(input, &content, e);
+  let sidecar_path = sidecar_path(input);
+  let sidecar = read_sidecar(&sidecar_path)?;
+  // where the front matter starts, for errors found once it has been read
+  let header_start = stream.header_start();
+  let mut metadata = match &sidecar {
+    Some(sidecar) => {
+      let at_sidecar = |e| SourceError::new(&sidecar_path, sidecar, e);
+      let sidecar_yaml = metadata::parse_mapping(sidecar).map_err(|e| at_sidecar(ParseError::WrongYaml(e, 0)))?;
+      let inline_yaml = if stream.has_header() {
+        let raw = stream.read_raw_header().map_err(at_input)?;
+        let offset = stream.offset_of(raw);
+        Some(metadata::parse_mapping(raw).map_err(|e| at_input(ParseError::WrongYaml(e, offset)))?)
+      }
+      else {
+        None
+      };
+      metadata::merge_sidecar(sidecar_yaml, inline_yaml).map_err(|e| at_sidecar(ParseError::WrongYaml(e, 0)))?
     },
-    Err(e) => {
-      println!("{:?}", e);
-      Err(std::io::Error::new(std::io::ErrorKind::Other, format!("{:?}", e)))
+    None if options.allow_missing_header && !stream.has_header() => {
+      let mut metadata = Metadata::from_body(stream.current(), &post_name.slug);
+      if metadata.date.is_none() && post_name.date.is_none() {
+        let modified: chrono::DateTime<chrono::Local> = std::fs::metadata(input)?.modified()?.into();
+        metadata.date = Some(modified.format("%Y-%m-%d").to_string());
+      }
+      warnings.push(format!("no front matter, made up title {:?} and date {}",
+                            metadata.title, metadata.date.as_ref().or(post_name.date.as_ref()).unwrap()));
+      metadata
     },
+    None => stream.read_header().map_err(at_input)?,
+  };
+  metadata.take_taxonomies(&options.taxonomies).map_err(|e| match &sidecar {
+    Some(sidecar) => SourceError::new(&sidecar_path, sidecar, ParseError::WrongYaml(e, 0)),
+    None => at_input(ParseError::WrongYaml(e, header_start)),
+  })?;
+  let category = options.site.as_ref().filter(|site| site.categories).and(input.parent()).and_then(|dir| dir.file_name());
+  if let Some(category) = category {
+    metadata.set_category(&category.to_string_lossy());
+  }
+  options.fields.materialize(&mut metadata.extra);
+  if let Some(date) = metadata.normalize_date() {
+    warnings.push(format!("date {:?} is in none of the formats hakyll reads, moved under [extra]", date));
+  }
+  if metadata.date.is_none() {
+    metadata.date = post_name.date;
+  }
+  metadata.slug.get_or_insert(post_name.slug);
+  // `None` without a site.hs to take routes from
+  let route = options.site.as_ref().map(|site| match identifier(input, &options.site_root) {
+    Some(identifier) => site.route(&identifier).unwrap_or_else(|| Err(format!("no rule in site.hs matches {:?}", identifier))),
+    None => Err(format!("{} is outside of the hakyll site", input.display())),
+  });
+  metadata.alias = match (route, &options.alias) {
+    (Some(Ok(route)), _) => Some(format!("/{}", route)),
+    (route, alias) => {
+      if let Some(Err(e)) = route {
+        warnings.push(format!("no route: {}", e));
+      }
+      let alias_context = alias::AliasContext {
+        date: metadata.date.as_deref(),
+        slug: metadata.slug.as_deref().unwrap(),
+        stem: input.file_stem().and_then(|stem| stem.to_str()).unwrap_or(""),
+        dir,
+      };
+      match alias.as_ref().map(|alias| alias.render(&alias_context)) {
+        Some(Ok(alias)) => Some(alias),
+        Some(Err(e)) => {
+          warnings.push(format!("no alias: {}", e));
+          None
+        },
+        None => None,
+      }
+    },
+  };
+  let (converted, touched) = pandoc::convert(stream.current());
+  if !touched.is_empty() {
+    warnings.push(pandoc::describe(&touched));
+  }
+  let mut body = markdown::normalize_teaser(&converted, &options.teaser_separators);
+  let mut post_assets = Vec::new();
+  let mut bundle = options.bundle;
+  if let Some(placement) = &options.assets {
+    let base = metadata.alias.clone().or_else(|| identifier(input, &options.site_root));
+    let (relocated, found, asset_warnings) = assets::relocate(&body, base.as_deref(), &options.site_root, placement);
+    // assets can only sit next to a page in a bundle
+    bundle |= *placement == assets::Placement::Bundle && !found.is_empty();
+    body = relocated;
+    post_assets = found;
+    warnings.extend(asset_warnings);
   }
+  let output = if bundle { bundle_path(output, metadata.slug.as_deref().unwrap()) } else { output.to_path_buf() };
+  let redirect = match (&metadata.alias, output.strip_prefix(&options.content_root)) {
+    (Some(alias), Ok(relative)) => Some(redirects::Redirect {
+      from: alias.clone(),
+      to: format!("/{}", check::page_url(relative, metadata.slug.as_deref(), None)),
+    }),
+    (Some(_), Err(_)) => {
+      warnings.push(format!("no redirect: {} is outside of {}", output.display(), options.content_root.display()));
+      None
+    },
+    (None, _) => None,
+  };
+  Ok(Post { input: input.to_path_buf(), output, content, body, metadata, redirect, assets: post_assets, warnings })
 }
 
-fn print_list_toml(elements: Vec<&str>) -> String {
+/// Write out `post`, with its links to the posts in `links` turned into zola internal links.
+fn write_post(post: Post, links: Option<&links::LinkIndex>, options: &Options) -> ConvertResult<Conversion> {
+  let mut warnings = post.warnings.clone();
+  let body = match links {
+    Some(links) => {
+      let (body, link_warnings) = links.rewrite(&post.body, post.metadata.alias.as_deref());
+      warnings.extend(link_warnings);
+      body
+    },
+    None => post.body.clone(),
+  };
   let mut buf = String::new();
-  buf.push('[');
-  let str_elements: Vec<String> = elements.iter().map(|t| format!("\"{}\"", t)).collect();
-  buf.push_str(str_elements.join(",").as_str());
-  buf.push(']');
-  buf
+  buf.push_str(&post.metadata.format_header()?);
+  if !body.starts_with('\n') && !body.starts_with("\r\n") {
+    // a post whose metadata lives in a sidecar starts right with its body
+    buf.push('\n');
+  }
+  buf.push_str(&body);
+  if options.dry_run {
+    println!("{} -> {}", post.input.display(), post.output.display());
+    print!("{}", unified_diff(&post.input, &post.output, &post.content, &buf));
+  }
+  else {
+    std::fs::create_dir_all(post.output.parent().unwrap())?;
+    std::fs::write(&post.output, buf)?;
+  }
+  for asset in &post.assets {
+    let target = options.assets.as_ref().unwrap().target(asset, &post.output);
+    if options.dry_run {
+      println!("copy {} -> {}", asset.source.display(), target.display());
+    }
+    else {
+      std::fs::create_dir_all(target.parent().unwrap())?;
+      std::fs::copy(&asset.source, &target)?;
+    }
+  }
+  Ok(Conversion { metadata: post.metadata, redirect: post.redirect, warnings })
 }
 
-fn print_tags_as_toml(tags: &String) -> String {
-  let mut tag_vec = Vec::new();
-  tags.split(", ").for_each(|tag| {
-    tag_vec.push(tag)
-  });
-  print_list_toml(tag_vec)
+fn unified_diff(input: &Path, output: &Path, before: &str, after: &str) -> String {
+  similar::TextDiff::from_lines(before, after)
+    .unified_diff()
+    .header(&input.display().to_string(), &output.display().to_string())
+    .to_string()
 }
 
-#[derive(Debug, Eq, PartialEq, Serialize, Deserialize)]
-struct Metadata {
-  title: String,
-  date: Option<String>,
-  tags: Option<String>,
-  alias: Option<String>,
+#[derive(Debug)]
+enum ParseError {
+  /// What went wrong and the byte offset in the post where it did.
+  BadSyntax(String, usize),
+  /// The byte offset is where the yaml that failed starts.
+  WrongYaml(serde_yaml::Error, usize),
 }
 
-impl Metadata {
-  fn format_header(&self) -> String {
-    let mut buf = String::new();
-    buf.push_str("+++\n");
-    buf.push_str(format!("title = \"{}\"\n", &self.title).as_str());
-    if let Some(date) = &self.date {
-      buf.push_str(format!("date = {}\n", date).as_str());
+impl ParseError {
+  /// 1-based line and column in `content`.
+  fn position(&self, content: &str) -> (usize, usize) {
+    match self {
+      ParseError::BadSyntax(_, offset) => line_column(content, *offset),
+      ParseError::WrongYaml(e, offset) => {
+        let (line, column) = line_column(content, *offset);
+        match e.location() {
+          Some(location) if location.line() > 1 => (line + location.line() - 1, location.column()),
+          Some(location) => (line, column + location.column() - 1),
+          None => (line, column),
+        }
+      },
+    }
+  }
+}
+
+impl ParseError {
+  fn kind(&self) -> &'static str {
+    match self {
+      ParseError::BadSyntax(_, _) => "bad syntax",
+      ParseError::WrongYaml(_, _) => "wrong yaml",
     }
-    if let Some(alias) = &self.alias {
-      buf.push_str(format!("aliases = [\"{}\"]\n", alias).as_str());
+  }
+}
+
+impl std::fmt::Display for ParseError {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    match self {
+      ParseError::BadSyntax(msg, _) => write!(f, "{}: {}", self.kind(), msg),
+      ParseError::WrongYaml(e, _) => {
+        // serde_yaml counts lines from the start of the header rather than the file
+        let msg = e.to_string();
+        write!(f, "{}: {}", self.kind(), msg.split(" at line ").next().unwrap())
+      },
     }
-    if let Some(tags) = &self.tags {
-      buf.push_str("[taxonomies]\n");
-      buf.push_str(format!("tags = {}\n", print_tags_as_toml(tags)).as_str());
+  }
+}
+
+impl std::error::Error for ParseError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      ParseError::BadSyntax(_, _) => None,
+      ParseError::WrongYaml(e, _) => Some(e),
     }
-    buf.push_str("+++");
-    buf
   }
 }
 
+fn line_column(content: &str, offset: usize) -> (usize, usize) {
+  let before = &content[..offset];
+  let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+  (before.matches('\n').count() + 1, before[line_start..].chars().count() + 1)
+}
+
+type ParseResult<T> = Result<T, ParseError>;
+
+/// A `ParseError` pinned to the file it came from, shown like a compiler diagnostic.
 #[derive(Debug)]
-enum ParseError {
-  BadSyntax(String),
-  WrongYaml(serde_yaml::Error),
+struct SourceError {
+  path: PathBuf,
+  line: usize,
+  column: usize,
+  /// The offending line and the one before it, with their line numbers.
+  lines: Vec<(usize, String)>,
+  error: ParseError,
 }
 
-impl From<serde_yaml::Error> for ParseError {
-  fn from(e: serde_yaml::Error) -> Self {
-    ParseError::WrongYaml(e)
+impl SourceError {
+  fn new(path: &Path, content: &str, error: ParseError) -> SourceError {
+    let (line, column) = error.position(content);
+    let lines = content.lines().enumerate()
+      .map(|(idx, text)| (idx + 1, text.trim_end().to_string()))
+      .skip(line.saturating_sub(2))
+      .take(if line > 1 { 2 } else { 1 })
+      .collect();
+    SourceError { path: path.to_path_buf(), line, column, lines, error }
   }
 }
 
-type ParseResult<T> = Result<T, ParseError>;
+impl std::fmt::Display for SourceError {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    let width = self.line.to_string().len();
+    writeln!(f, "{}", self.error)?;
+    write!(f, "{:width$}--> {}:{}:{}", "", self.path.display(), self.line, self.column, width = width)?;
+    if self.lines.is_empty() {
+      return Ok(());
+    }
+    write!(f, "\n{:width$} |", "", width = width)?;
+    for (number, text) in &self.lines {
+      write!(f, "\n{:>width$} | {}", number, text, width = width)?;
+    }
+    write!(f, "\n{:width$} | {:>column$}", "", "^", width = width, column = self.column)
+  }
+}
+
+impl std::error::Error for SourceError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    Some(&self.error)
+  }
+}
+
+#[derive(Debug)]
+enum ConvertError {
+  Io(std::io::Error),
+  Parse(SourceError),
+  Toml(toml::ser::Error),
+  /// Another post of the batch, named here, already goes to the same page or url.
+  Clash(PathBuf),
+}
+
+impl ConvertError {
+  /// What the error report groups failures by.
+  fn kind(&self) -> &'static str {
+    match self {
+      ConvertError::Io(_) => "io error",
+      ConvertError::Parse(e) => e.error.kind(),
+      ConvertError::Toml(_) => "cannot write front matter",
+      ConvertError::Clash(_) => "duplicate page",
+    }
+  }
+}
+
+impl std::fmt::Display for ConvertError {
+  fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+    match self {
+      ConvertError::Io(e) => write!(f, "{}: {}", self.kind(), e),
+      ConvertError::Parse(e) => e.fmt(f),
+      ConvertError::Toml(e) => write!(f, "{}: {}", self.kind(), e),
+      ConvertError::Clash(other) => write!(f, "{}: {} already goes to the same page", self.kind(), other.display()),
+    }
+  }
+}
+
+impl std::error::Error for ConvertError {
+  fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+    match self {
+      ConvertError::Io(e) => Some(e),
+      ConvertError::Parse(e) => Some(e),
+      ConvertError::Toml(e) => Some(e),
+      ConvertError::Clash(_) => None,
+    }
+  }
+}
+
+impl From<std::io::Error> for ConvertError {
+  fn from(e: std::io::Error) -> Self {
+    ConvertError::Io(e)
+  }
+}
+
+impl From<SourceError> for ConvertError {
+  fn from(e: SourceError) -> Self {
+    ConvertError::Parse(e)
+  }
+}
+
+impl From<toml::ser::Error> for ConvertError {
+  fn from(e: toml::ser::Error) -> Self {
+    ConvertError::Toml(e)
+  }
+}
+
+type ConvertResult<T> = Result<T, ConvertError>;
 
 struct Stream<'a> {
   offset: usize,
-  content: &'a String
+  content: &'a str
 }
 
 impl <'a> Stream<'a> {
-  fn new(content: &'a String) -> Stream<'a> {
+  fn new(content: &'a str) -> Stream<'a> {
     Stream {
       offset: 0,
-      content: content,
+      content,
     }
   }
 
   fn read_header(&mut self) -> ParseResult<Metadata> {
-    let _start_mark = self.read_string("---")?;
-    let metadata_raw = self.read_until("---")?;
-    let _end_mark = self.read_string("---")?;
-    let metadata = serde_yaml::from_str(metadata_raw)?;
+    let metadata_raw = self.read_raw_header()?;
+    let metadata = serde_yaml::from_str(metadata_raw).map_err(|e| ParseError::WrongYaml(e, self.offset_of(metadata_raw)))?;
     Ok(metadata)
   }
 
+  /// Yaml between the `---` lines, left unparsed. The header may come after a byte order
+  /// mark and blank lines, and may be closed by yaml's `...` end marker instead of `---`.
+  fn read_raw_header(&mut self) -> ParseResult<&'a str> {
+    self.offset = self.header_start();
+    let _start_mark = self.read_string("---")?;
+    let rest_of_line = self.current().split('\n').next().unwrap();
+    if !rest_of_line.trim().is_empty() {
+      return Err(ParseError::BadSyntax(format!("expected a line break after \"---\" but got {:?}", rest_of_line), self.offset));
+    }
+    let metadata_raw = self.read_until(&["---", "..."])?;
+    let _end_mark = self.read_string("---").or_else(|_| self.read_string("..."))?;
+    Ok(metadata_raw)
+  }
+
+  fn has_header(&self) -> bool {
+    let first_line = self.content[self.header_start()..].split('\n').next().unwrap();
+    first_line.trim_end() == "---"
+  }
+
+  /// Offset of the opening `---`, skipping a byte order mark and blank lines.
+  fn header_start(&self) -> usize {
+    let mut offset = self.offset;
+    if self.content[offset..].starts_with('\u{feff}') {
+      offset += '\u{feff}'.len_utf8();
+    }
+    loop {
+      let rest = &self.content[offset..];
+      match rest.find('\n') {
+        Some(idx) if rest[..idx].trim().is_empty() => offset += idx + 1,
+        _ => return offset,
+      }
+    }
+  }
+
+  /// Byte offset of `slice`, which has to be a slice of this stream's content.
+  fn offset_of(&self, slice: &str) -> usize {
+    slice.as_ptr() as usize - self.content.as_ptr() as usize
+  }
+
   fn current(&self) -> &'a str {
     self.content.get(self.offset..).unwrap()
   }
@@ -126,32 +753,41 @@ impl <'a> Stream<'a> {
     match self.current().get(0..len) {
       Some(sub) => {
         if s == sub {
-          self.offset = self.offset + len;
+          self.offset += len;
           Ok(sub)
         }
         else {
-          Err(ParseError::BadSyntax(format!("expected {:?} but got {:?}", s, sub)))
+          Err(ParseError::BadSyntax(format!("expected {:?} but got {:?}", s, sub), self.offset))
         }
       },
-      None => Err(ParseError::BadSyntax(format!("unexpected end of input to read {:?}", s)))
+      None => Err(ParseError::BadSyntax(format!("unexpected end of input to read {:?}", s), self.offset))
     }
   }
 
-  fn read_until(&mut self, s: &str) -> ParseResult<&'a str> {
-    match self.current().find(s) {
-      Some(idx) => {
-        let sliced = self.current().get(0..idx).unwrap();
-        self.offset = self.offset + idx;
-        Ok(sliced)
-      },
-      None => Err(ParseError::BadSyntax(format!("expected \"{:?}\" but not found", s))),
+  /// Read up to the first line consisting of one of `marks`, leaving the mark itself unread.
+  fn read_until(&mut self, marks: &[&str]) -> ParseResult<&'a str> {
+    let current = self.current();
+    let mut line_start = 0;
+    loop {
+      let line_end = current[line_start..].find('\n').map_or(current.len(), |idx| line_start + idx);
+      if marks.contains(&current[line_start..line_end].trim_end()) {
+        self.offset += line_start;
+        return Ok(&current[..line_start]);
+      }
+      if line_end == current.len() {
+        return Err(ParseError::BadSyntax(format!("expected a line of {:?} but not found", marks), self.offset + current.len()));
+      }
+      line_start = line_end + 1;
     }
   }
 }
 
 #[cfg(test)]
 mod tests {
-  use super::{Stream, Metadata};
+  use std::collections::BTreeMap;
+  use std::path::Path;
+  use super::{Stream, Metadata, Options, SourceError, bundle_path, content_dir_of, read_post, unified_diff};
+  use super::metadata::Terms;
   #[test]
   fn test_read_string() {
     let s = String::from("---\ntitle: タイトル");
@@ -163,7 +799,7 @@ mod tests {
   fn test_read_until() {
     let s = String::from("\ntitle: タイトル\n---\n");
     let mut stream = Stream::new(&s);
-    assert_eq!(stream.read_until("---").unwrap(), "\ntitle: タイトル\n");
+    assert_eq!(stream.read_until(&["---"]).unwrap(), "\ntitle: タイトル\n");
     assert_eq!(stream.read_string("---").unwrap(), "---");
   }
 
@@ -176,6 +812,9 @@ mod tests {
       date: None,
       tags: None,
       alias: None,
+      slug: None,
+      taxonomies: BTreeMap::new(),
+      extra: BTreeMap::new(),
     });
   }
 
@@ -186,8 +825,157 @@ mod tests {
     assert_eq!(stream.read_header().unwrap(), Metadata {
       title: String::from("『ビッグデータを支える技術』を読んだ データインジェスチョンについて"),
       date: Some(String::from("2020-02-01")),
-      tags: Some(String::from("database, book")),
+      tags: Some(Terms(vec![String::from("database"), String::from("book")])),
       alias: None,
+      slug: None,
+      taxonomies: BTreeMap::new(),
+      extra: BTreeMap::new(),
     });
   }
-}
\ No newline at end of file
+
+  fn title_of(s: &str) -> (String, String) {
+    let s = String::from(s);
+    let mut stream = Stream::new(&s);
+    let metadata = stream.read_header().unwrap();
+    (metadata.title, stream.current().to_string())
+  }
+
+  #[test]
+  fn test_read_header_bom() {
+    assert_eq!(title_of("\u{feff}---\ntitle: タイトル\n---\nbody"), (String::from("タイトル"), String::from("\nbody")));
+  }
+
+  #[test]
+  fn test_read_header_crlf() {
+    assert_eq!(title_of("---\r\ntitle: タイトル\r\n---\r\nbody"), (String::from("タイトル"), String::from("\r\nbody")));
+  }
+
+  #[test]
+  fn test_read_header_yaml_end_marker() {
+    assert_eq!(title_of("---\ntitle: タイトル\n...\nbody"), (String::from("タイトル"), String::from("\nbody")));
+  }
+
+  #[test]
+  fn test_read_header_leading_blank_lines() {
+    assert_eq!(title_of("\n  \n---\ntitle: タイトル\n---\nbody"), (String::from("タイトル"), String::from("\nbody")));
+  }
+
+  #[test]
+  fn test_read_header_mark_inside_value() {
+    assert_eq!(title_of("---\ntitle: before---after\ndescription: \"--- not the end\"\n---\nbody --- more"),
+               (String::from("before---after"), String::from("\nbody --- more")));
+  }
+
+  #[test]
+  fn test_read_header_unclosed() {
+    let s = String::from("---\ntitle: タイトル\nbody --- more");
+    let mut stream = Stream::new(&s);
+    assert!(stream.read_header().is_err());
+    let s = String::from("--- title: タイトル\n---\n");
+    let mut stream = Stream::new(&s);
+    assert!(stream.read_header().is_err());
+  }
+
+  #[test]
+  fn test_has_header() {
+    assert!(Stream::new("\u{feff}\r\n---\r\ntitle: t\r\n---\r\n").has_header());
+    assert!(!Stream::new("# heading\n---\n").has_header());
+    assert!(!Stream::new("----\n").has_header());
+  }
+
+  fn error_of(s: &str) -> String {
+    let mut stream = Stream::new(s);
+    let e = stream.read_header().unwrap_err();
+    SourceError::new(Path::new("posts/foo.md"), s, e).to_string()
+  }
+
+  #[test]
+  fn test_source_error_yaml() {
+    assert_eq!(error_of("---\ntitle: foo\ndate: a: b\n---\nbody"), "\
+      wrong yaml: mapping values are not allowed in this context\n \
+       --> posts/foo.md:3:8\n  \
+        |\n\
+      2 | title: foo\n\
+      3 | date: a: b\n  \
+        |        ^");
+  }
+
+  #[test]
+  fn test_source_error_missing_field() {
+    assert_eq!(error_of("\n---\nauthor: utky\n---\n"), "\
+      wrong yaml: missing field `title`\n \
+       --> posts/foo.md:3:7\n  \
+        |\n\
+      2 | ---\n\
+      3 | author: utky\n  \
+        |       ^");
+  }
+
+  #[test]
+  fn test_source_error_syntax() {
+    assert_eq!(error_of("---\ntitle: foo\nbody"), "\
+      bad syntax: expected a line of [\"---\", \"...\"] but not found\n \
+       --> posts/foo.md:3:5\n  \
+        |\n\
+      2 | title: foo\n\
+      3 | body\n  \
+        |     ^");
+  }
+
+  #[test]
+  fn test_taxonomy_error_location() {
+    let dir = std::env::temp_dir().join(format!("hakyell2zola-taxonomy-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let (inline, with_sidecar) = (dir.join("inline.md"), dir.join("sidecar.md"));
+    std::fs::write(&inline, "\n---\ntitle: foo\ncategories: {a: 1}\n---\nbody\n").unwrap();
+    std::fs::write(&with_sidecar, "body\n").unwrap();
+    std::fs::write(dir.join("sidecar.md.metadata"), "title: foo\ncategories: {a: 1}\n").unwrap();
+    let options = Options { taxonomies: vec![String::from("categories")], ..Options::default() };
+    let error_of = |input: &Path| read_post(input, &dir.join("out.md"), Path::new(""), &options).err().unwrap().to_string();
+    assert!(error_of(&inline).contains(&format!("--> {}:2:1", inline.display())));
+    assert!(error_of(&with_sidecar).contains(&format!("--> {}.metadata:1:1", with_sidecar.display())));
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn test_build_categories() {
+    let dir = std::env::temp_dir().join(format!("hakyell2zola-categories-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("rust")).unwrap();
+    std::fs::write(dir.join("rust/foo.md"), "---\ntitle: foo\ncategories: ignored\n---\nbody\n").unwrap();
+    let site = super::site::Site::parse("categories <- buildCategories \"posts/*/*\" (fromCapture \"categories/*.html\")\n");
+    let options = Options { site: Some(site), ..Options::default() };
+    let post = read_post(&dir.join("rust/foo.md"), &dir.join("out.md"), Path::new("rust"), &options).unwrap();
+    assert_eq!(post.metadata.taxonomies.get("categories"), Some(&Terms(vec![String::from("rust")])));
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+
+  #[test]
+  fn test_unified_diff() {
+    let before = "---\ntitle: タイトル\n---\nbody\n";
+    let after = "+++\ntitle = \"タイトル\"\n+++\nbody\n";
+    assert_eq!(unified_diff(Path::new("posts/foo.md"), Path::new("content/foo.md"), before, after), "\
+      --- posts/foo.md\n\
+      +++ content/foo.md\n\
+      @@ -1,4 +1,4 @@\n\
+      ----\n\
+      -title: タイトル\n\
+      ----\n\
+      ++++\n\
+      +title = \"タイトル\"\n\
+      ++++\n \
+       body\n");
+  }
+
+  #[test]
+  fn test_bundle_path() {
+    assert_eq!(bundle_path(Path::new("content/posts/hello.md"), "hello-world"), Path::new("content/posts/hello-world/index.md"));
+    assert_eq!(bundle_path(Path::new("hello.md"), "hello"), Path::new("hello/index.md"));
+  }
+
+  #[test]
+  fn test_content_dir_of() {
+    assert_eq!(content_dir_of(Path::new("site/content/posts/2019")), Some(Path::new("site/content")));
+    assert_eq!(content_dir_of(Path::new("content")), Some(Path::new("content")));
+    assert_eq!(content_dir_of(Path::new("out/posts")), None);
+  }
+}
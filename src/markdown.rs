@@ -0,0 +1,121 @@
+//! Bits of markdown the body rewrites share: telling prose from code and finding urls.
+use std::sync::OnceLock;
+use regex::Regex;
+
+/// Run `f` over every run of lines of `body` outside fenced code blocks, which are kept as
+/// they are. Runs are passed with their line breaks.
+pub fn map_text_blocks(body: &str, mut f: impl FnMut(&str) -> String) -> String {
+  let mut mapped = String::with_capacity(body.len());
+  let mut fence: Option<&str> = None;
+  let mut text_start = 0;
+  let mut offset = 0;
+  for line in body.split_inclusive('\n') {
+    let trimmed = line.trim_start();
+    if let Some(mark) = fence {
+      if trimmed.starts_with(mark) {
+        fence = None;
+        text_start = offset + line.len();
+      }
+      mapped.push_str(line);
+    }
+    else if let Some(mark) = ["```", "~~~"].iter().find(|mark| trimmed.starts_with(**mark)) {
+      fence = Some(mark);
+      mapped.push_str(&f(&body[text_start..offset]));
+      mapped.push_str(line);
+    }
+    offset += line.len();
+  }
+  if fence.is_none() {
+    mapped.push_str(&f(&body[text_start..]));
+  }
+  mapped
+}
+
+/// Run `f` over every line of `body` outside fenced code blocks, which are kept as they are.
+/// Lines are passed with their line break.
+pub fn map_text_lines(body: &str, mut f: impl FnMut(&str) -> String) -> String {
+  map_text_blocks(body, |text| text.split_inclusive('\n').map(&mut f).collect())
+}
+
+/// What zola cuts `page.summary` at.
+pub const SUMMARY_MARKER: &str = "<!-- more -->";
+
+/// Turn the teaser `separators` of hakyll into zola's summary marker.
+pub fn normalize_teaser(body: &str, separators: &[String]) -> String {
+  map_text_lines(body, |line| {
+    separators.iter().fold(line.to_string(), |line, separator| line.replace(separator.as_str(), SUMMARY_MARKER))
+  })
+}
+
+/// Patterns capturing a url as their second group, with what comes before and after it as
+/// the first and third.
+pub fn url_patterns() -> &'static [Regex; 3] {
+  static PATTERNS: OnceLock<[Regex; 3]> = OnceLock::new();
+  PATTERNS.get_or_init(|| [
+    // `[text](url "title")` and `![alt](url)`
+    Regex::new(r#"(\]\(\s*<?)([^)\s>]+)(>?(?:\s+"[^"]*")?\s*\))"#).unwrap(),
+    // `[label]: url`
+    Regex::new(r#"^( {0,3}\[[^\]]+\]:\s*<?)([^\s>]+)(>?)"#).unwrap(),
+    Regex::new(r#"(\b(?:href|src)\s*=\s*")([^"]+)(")"#).unwrap(),
+  ])
+}
+
+/// Whether `url` points somewhere else than the site, or nowhere relative to a page.
+pub fn is_external(url: &str) -> bool {
+  url.starts_with('#') || url.starts_with("@/") || url.starts_with("//") || url.contains(':')
+}
+
+/// `posts/` for `posts/foo.html`, empty for a top level page.
+pub fn dir_of(url: &str) -> &str {
+  url.rfind('/').map_or("", |idx| &url[..=idx])
+}
+
+/// Fold `.` and `..` away. `None` when `..` would leave the site.
+pub fn resolve_dots(path: &str) -> Option<String> {
+  let mut segments: Vec<&str> = Vec::new();
+  for segment in path.split('/') {
+    match segment {
+      "." => {},
+      ".." => {
+        segments.pop()?;
+      },
+      _ => segments.push(segment),
+    }
+  }
+  Some(segments.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{map_text_blocks, map_text_lines, normalize_teaser, resolve_dots};
+
+  #[test]
+  fn test_map_text_lines() {
+    let body = "a\n```rust\na\n```\n~~~\na\n~~~\na";
+    assert_eq!(map_text_lines(body, |line| line.replace('a', "b")), "b\n```rust\na\n```\n~~~\na\n~~~\nb");
+  }
+
+  #[test]
+  fn test_map_text_blocks() {
+    let mut blocks = Vec::new();
+    let body = "a\nb\n```\nc\n```\nd";
+    assert_eq!(map_text_blocks(body, |text| {
+      blocks.push(text.to_string());
+      text.to_uppercase()
+    }), "A\nB\n```\nc\n```\nD");
+    assert_eq!(blocks, vec!["a\nb\n", "d"]);
+  }
+
+  #[test]
+  fn test_resolve_dots() {
+    assert_eq!(resolve_dots("posts/../images/./a.png").as_deref(), Some("images/a.png"));
+    assert_eq!(resolve_dots("../a.png"), None);
+  }
+
+  #[test]
+  fn test_normalize_teaser() {
+    let separators = vec![String::from("<!--more-->"), String::from("<!--cut-->")];
+    assert_eq!(normalize_teaser("intro\n<!--more-->\nrest", &separators), "intro\n<!-- more -->\nrest");
+    assert_eq!(normalize_teaser("intro <!--cut-->\n```\n<!--more-->\n```\n", &separators), "intro <!-- more -->\n```\n<!--more-->\n```\n");
+  }
+}
@@ -0,0 +1,449 @@
+use serde::{Serialize, Deserialize, Deserializer};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The taxonomy posts are filed under by `set_category`.
+pub const CATEGORIES: &str = "categories";
+
+#[derive(Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Metadata {
+  pub title: String,
+  pub date: Option<String>,
+  pub tags: Option<Terms>,
+  pub alias: Option<String>,
+  pub slug: Option<String>,
+  /// Taxonomies other than tags, picked out of `extra` by `take_taxonomies`.
+  #[serde(skip)]
+  pub taxonomies: BTreeMap<String, Terms>,
+  /// Front matter keys zola doesn't know about, kept for templates under `[extra]`.
+  #[serde(flatten)]
+  pub extra: BTreeMap<String, serde_yaml::Value>,
+}
+
+/// Terms of a taxonomy, written in hakyll either as one string like `a, b` or as a yaml list.
+#[derive(Debug, Default, Eq, PartialEq, Serialize)]
+pub struct Terms(pub Vec<String>);
+
+impl Terms {
+  fn push(&mut self, term: &str) {
+    let term = term.trim();
+    if !term.is_empty() && !self.0.iter().any(|t| t == term) {
+      self.0.push(term.to_string());
+    }
+  }
+
+  /// Split on `,` as well as the japanese `、`, since `buildTags` only cares about the former
+  /// but posts written with an IME often use the latter.
+  fn push_all(&mut self, terms: &str) {
+    terms.split([',', '、', '，']).for_each(|term| self.push(term));
+  }
+}
+
+impl<'de> Deserialize<'de> for Terms {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    use serde::de::Error;
+    use serde_yaml::Value;
+    fn scalar(value: &Value) -> Option<String> {
+      match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+      }
+    }
+    fn described(value: &Value) -> &'static str {
+      match value {
+        Value::Null => "nothing",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Sequence(_) => "a list",
+        Value::Mapping(_) => "a mapping",
+      }
+    }
+
+    let mut terms = Terms::default();
+    match Value::deserialize(deserializer)? {
+      Value::Null => {},
+      Value::Sequence(seq) => {
+        for value in &seq {
+          // hakyll's `lookupStringList` keeps the items of a list whole
+          let term = scalar(value).ok_or_else(|| D::Error::custom(format!("expected a term but got {}", described(value))))?;
+          terms.push(&term);
+        }
+      },
+      value => {
+        let term = scalar(&value).ok_or_else(|| D::Error::custom(format!("expected terms but got {}", described(&value))))?;
+        terms.push_all(&term);
+      },
+    }
+    Ok(terms)
+  }
+}
+
+/// Zola front matter as it is written between the `+++` lines.
+#[derive(Serialize)]
+struct FrontMatter<'a> {
+  title: &'a str,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  date: Option<toml::Value>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  slug: Option<&'a str>,
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  aliases: Vec<&'a str>,
+  #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+  taxonomies: BTreeMap<&'a str, Vec<&'a str>>,
+  /// A `toml::Value` rather than a map, so that nested tables are written after plain values.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  extra: Option<toml::Value>,
+}
+
+impl Metadata {
+  /// Metadata for a post without front matter, titled after its first `#` heading
+  /// or, failing that, after `fallback_title`.
+  pub fn from_body(body: &str, fallback_title: &str) -> Metadata {
+    let heading = body.lines()
+      .filter_map(|line| line.strip_prefix("# "))
+      .map(|title| title.trim().trim_end_matches('#').trim_end())
+      .find(|title| !title.is_empty());
+    let title = match heading {
+      Some(title) => title.to_string(),
+      None => fallback_title.replace(['-', '_'], " "),
+    };
+    Metadata { title, ..Metadata::default() }
+  }
+
+  /// Move the front matter keys listed in `keys`, e.g. `categories`, from `extra` into zola
+  /// taxonomies of the same name.
+  pub fn take_taxonomies(&mut self, keys: &[String]) -> Result<(), serde_yaml::Error> {
+    for key in keys {
+      if let Some(value) = self.extra.remove(key) {
+        let terms = Terms::deserialize(value)?;
+        if !terms.0.is_empty() {
+          self.taxonomies.insert(key.clone(), terms);
+        }
+      }
+    }
+    Ok(())
+  }
+
+  /// File the post under `category` of the `categories` taxonomy, the way hakyll's
+  /// `buildCategories` does by the directory of the post rather than by a front matter key.
+  pub fn set_category(&mut self, category: &str) {
+    self.taxonomies.insert(String::from(CATEGORIES), Terms(vec![category.to_string()]));
+  }
+
+  pub fn taxonomy_names(&self) -> BTreeSet<String> {
+    let mut names: BTreeSet<String> = self.taxonomies.keys().cloned().collect();
+    if self.tags.as_ref().is_some_and(|tags| !tags.0.is_empty()) {
+      names.insert(String::from("tags"));
+    }
+    names
+  }
+
+  /// Write `date` the way toml reads it. A date hakyll couldn't read either is moved under
+  /// `extra` and returned, so that zola gets a page it builds.
+  pub fn normalize_date(&mut self) -> Option<String> {
+    let date = self.date.take()?;
+    match parse_date(&date) {
+      Some(datetime) => {
+        self.date = Some(datetime.to_string());
+        None
+      },
+      None => {
+        self.extra.insert(String::from("date"), serde_yaml::Value::String(date.clone()));
+        Some(date)
+      },
+    }
+  }
+
+  pub fn format_header(&self) -> Result<String, toml::ser::Error> {
+    let mut taxonomies = BTreeMap::new();
+    // like `take_taxonomies` does for the others, so that only what `taxonomy_names` declares is used
+    if let Some(tags) = self.tags.as_ref().filter(|tags| !tags.0.is_empty()) {
+      taxonomies.insert("tags", tags.0.iter().map(|tag| tag.as_str()).collect());
+    }
+    for (name, terms) in &self.taxonomies {
+      taxonomies.insert(name.as_str(), terms.0.iter().map(|term| term.as_str()).collect());
+    }
+    let front_matter = FrontMatter {
+      title: &self.title,
+      date: match &self.date {
+        Some(date) => Some(toml::Value::Datetime(parse_date(date).ok_or_else(|| {
+          serde::ser::Error::custom(format!("date {:?} is in none of the formats hakyll reads", date))
+        })?)),
+        None => None,
+      },
+      slug: self.slug.as_deref(),
+      aliases: self.alias.iter().map(|alias| alias.as_str()).collect(),
+      taxonomies,
+      extra: if self.extra.is_empty() {
+        None
+      }
+      else {
+        let table = self.extra.iter()
+          .filter_map(|(key, value)| Some((key.clone(), to_toml_value(value)?)))
+          .collect();
+        Some(toml::Value::Table(table))
+      },
+    };
+
+    let mut buf = String::new();
+    buf.push_str("+++\n");
+    front_matter.serialize(toml::Serializer::new(&mut buf).pretty_string(true).pretty_string_literal(false))?;
+    buf.push_str("+++");
+    Ok(buf)
+  }
+}
+
+/// Metadata of a post that has a hakyll sidecar `.metadata` file. Keys in the inline
+/// header take precedence over the sidecar, as they do in hakyll's `loadMetadata`.
+pub fn merge_sidecar(sidecar: serde_yaml::Mapping, inline: Option<serde_yaml::Mapping>) -> Result<Metadata, serde_yaml::Error> {
+  let mut merged = sidecar;
+  for (key, value) in inline.unwrap_or_default() {
+    merged.insert(key, value);
+  }
+  serde_yaml::from_value(serde_yaml::Value::Mapping(merged))
+}
+
+/// Parse a header or sidecar file, which may be empty and so parse as null rather than a mapping.
+pub fn parse_mapping(yaml: &str) -> Result<serde_yaml::Mapping, serde_yaml::Error> {
+  if yaml.trim().is_empty() {
+    return Ok(serde_yaml::Mapping::new());
+  }
+  match serde_yaml::from_str(yaml)? {
+    serde_yaml::Value::Null => Ok(serde_yaml::Mapping::new()),
+    value => serde_yaml::from_value(value),
+  }
+}
+
+/// `[[taxonomies]]` entries for zola's `config.toml` declaring the given taxonomies.
+pub fn taxonomies_config(names: &BTreeSet<String>) -> String {
+  names.iter().map(|name| format!("[[taxonomies]]\nname = {}\n", toml::Value::String(name.clone()))).collect::<Vec<_>>().join("\n")
+}
+
+/// The formats hakyll's `dateField` reads a date in, other than the RFC 3339 ones toml reads
+/// itself, each with how to write it for toml. `%Z` is left to `parse_date`, as chrono
+/// can't read zone names.
+const HAKYLL_DATE_FORMATS: [(&str, &str); 9] = [
+  ("%a, %d %b %Y %H:%M:%S %z", "%Y-%m-%dT%H:%M:%S%:z"),
+  ("%Y-%m-%dT%H:%M:%S%z", "%Y-%m-%dT%H:%M:%S%:z"),
+  ("%Y-%m-%d %H:%M:%S%z", "%Y-%m-%dT%H:%M:%S%:z"),
+  ("%a, %d %b %Y %H:%M:%S", "%Y-%m-%dT%H:%M:%S"),
+  ("%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S"),
+  ("%B %e, %Y %l:%M %p", "%Y-%m-%dT%H:%M:%S"),
+  ("%B %e, %Y", "%Y-%m-%d"),
+  ("%b %d, %Y", "%Y-%m-%d"),
+  ("%b %e, %Y", "%Y-%m-%d"),
+];
+
+/// `date` as a toml datetime, in any of the formats hakyll reads.
+pub fn parse_date(date: &str) -> Option<toml::value::Datetime> {
+  use chrono::{DateTime, NaiveDate, NaiveDateTime};
+  let date = date.trim();
+  if let Ok(datetime) = date.parse() {
+    return Some(datetime);
+  }
+  // the zone names hakyll's `%Z` gets, which are all UTC
+  let (date, utc) = match date.strip_suffix("UTC").or_else(|| date.strip_suffix("GMT")) {
+    Some(date) => (date.trim_end(), "Z"),
+    None => (date, ""),
+  };
+  HAKYLL_DATE_FORMATS.iter().find_map(|(format, toml_format)| {
+    let formatted = if toml_format.ends_with('z') {
+      DateTime::parse_from_str(date, format).ok()?.format(toml_format).to_string()
+    }
+    else if toml_format.ends_with('S') {
+      format!("{}{}", NaiveDateTime::parse_from_str(date, format).ok()?.format(toml_format), utc)
+    }
+    else {
+      NaiveDate::parse_from_str(date, format).ok()?.format(toml_format).to_string()
+    };
+    formatted.parse().ok()
+  })
+}
+
+/// `None` for null, which toml has no way to express.
+fn to_toml_value(value: &serde_yaml::Value) -> Option<toml::Value> {
+  use serde_yaml::Value;
+  match value {
+    Value::Null => None,
+    Value::Bool(b) => Some(toml::Value::Boolean(*b)),
+    Value::Number(n) => {
+      if let Some(i) = n.as_i64() {
+        Some(toml::Value::Integer(i))
+      }
+      else {
+        n.as_f64().map(toml::Value::Float)
+      }
+    },
+    Value::String(s) => Some(toml::Value::String(s.clone())),
+    Value::Sequence(seq) => Some(toml::Value::Array(seq.iter().filter_map(to_toml_value).collect())),
+    Value::Mapping(map) => {
+      let table = map.iter().filter_map(|(k, v)| {
+        let key = match k {
+          Value::String(s) => s.clone(),
+          other => match to_toml_value(other)? {
+            toml::Value::String(s) => s,
+            other => other.to_string(),
+          },
+        };
+        Some((key, to_toml_value(v)?))
+      }).collect();
+      Some(toml::Value::Table(table))
+    },
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::{BTreeMap, BTreeSet};
+  use super::{Metadata, Terms, taxonomies_config, merge_sidecar, parse_date, parse_mapping};
+
+  fn front_matter(metadata: &Metadata) -> toml::Value {
+    let header = metadata.format_header().unwrap();
+    let body = header.strip_prefix("+++\n").unwrap().strip_suffix("+++").unwrap();
+    body.parse().unwrap()
+  }
+
+  #[test]
+  fn test_format_header() {
+    let metadata = Metadata {
+      title: String::from("タイトル"),
+      date: Some(String::from("2020-02-01")),
+      tags: Some(Terms(vec![String::from("database"), String::from("book")])),
+      alias: Some(String::from("/posts/2020-02-01-foo.html")),
+      slug: Some(String::from("foo")),
+      taxonomies: BTreeMap::new(),
+      extra: BTreeMap::new(),
+    };
+    assert_eq!(metadata.format_header().unwrap(), "+++\n\
+      title = \"タイトル\"\n\
+      date = 2020-02-01\n\
+      slug = \"foo\"\n\
+      aliases = [\"/posts/2020-02-01-foo.html\"]\n\
+      \n\
+      [taxonomies]\n\
+      tags = [\"database\", \"book\"]\n\
+      +++");
+  }
+
+  #[test]
+  fn test_format_header_empty_tags() {
+    let metadata: Metadata = serde_yaml::from_str("title: t\ntags: ''\n").unwrap();
+    assert!(metadata.taxonomy_names().is_empty());
+    assert!(front_matter(&metadata).get("taxonomies").is_none());
+  }
+
+  #[test]
+  fn test_format_header_escapes() {
+    let metadata = Metadata {
+      title: String::from("\"quoted\" C:\\path"),
+      date: Some(String::from("February 1, 2020")),
+      tags: Some(Terms(vec![String::from("c\"c"), String::from("back\\slash")])),
+      alias: None,
+      slug: None,
+      taxonomies: BTreeMap::new(),
+      extra: BTreeMap::new(),
+    };
+    let parsed = front_matter(&metadata);
+    assert_eq!(parsed["title"].as_str(), Some("\"quoted\" C:\\path"));
+    assert_eq!(parsed["date"].as_datetime().map(|date| date.to_string()).as_deref(), Some("2020-02-01"));
+    assert_eq!(parsed["taxonomies"]["tags"][0].as_str(), Some("c\"c"));
+    assert_eq!(parsed["taxonomies"]["tags"][1].as_str(), Some("back\\slash"));
+  }
+
+  #[test]
+  fn test_parse_date() {
+    let parsed = |date| parse_date(date).map(|date| date.to_string());
+    assert_eq!(parsed("2020-02-01T10:00:00+09:00").as_deref(), Some("2020-02-01T10:00:00+09:00"));
+    assert_eq!(parsed("2020-02-01 10:00:00").as_deref(), Some("2020-02-01T10:00:00"));
+    assert_eq!(parsed("Sat, 01 Feb 2020 10:00:00 UTC").as_deref(), Some("2020-02-01T10:00:00Z"));
+    assert_eq!(parsed("Sat, 01 Feb 2020 10:00:00 +0900").as_deref(), Some("2020-02-01T10:00:00+09:00"));
+    assert_eq!(parsed("February 1, 2020").as_deref(), Some("2020-02-01"));
+    assert_eq!(parsed("February 1, 2020 3:04 PM").as_deref(), Some("2020-02-01T15:04:00"));
+    assert_eq!(parsed("Feb 01, 2020").as_deref(), Some("2020-02-01"));
+    assert_eq!(parsed("first of February"), None);
+  }
+
+  #[test]
+  fn test_normalize_date() {
+    let mut metadata = Metadata { title: String::from("t"), date: Some(String::from("Feb 1, 2020")), ..Metadata::default() };
+    assert_eq!(metadata.normalize_date(), None);
+    assert_eq!(metadata.date.as_deref(), Some("2020-02-01"));
+    metadata.date = Some(String::from("someday"));
+    assert!(metadata.format_header().is_err());
+    assert_eq!(metadata.normalize_date().as_deref(), Some("someday"));
+    assert_eq!(metadata.date, None);
+    assert_eq!(front_matter(&metadata)["extra"]["date"].as_str(), Some("someday"));
+  }
+
+  #[test]
+  fn test_format_header_extra() {
+    let metadata: Metadata = serde_yaml::from_str("title: タイトル\nauthor: utky\ndraft: false\nimage:\n  src: cover.png\n  width: 640\nkeywords: [a, 1.5]\nempty: ~\ndescription: |\n  two\n  lines\n").unwrap();
+    let parsed = front_matter(&metadata);
+    let extra = &parsed["extra"];
+    assert_eq!(extra["author"].as_str(), Some("utky"));
+    assert_eq!(extra["draft"].as_bool(), Some(false));
+    assert_eq!(extra["image"]["src"].as_str(), Some("cover.png"));
+    assert_eq!(extra["image"]["width"].as_integer(), Some(640));
+    assert_eq!(extra["keywords"][0].as_str(), Some("a"));
+    assert_eq!(extra["keywords"][1].as_float(), Some(1.5));
+    assert_eq!(extra["description"].as_str(), Some("two\nlines\n"));
+    assert!(extra.get("empty").is_none());
+  }
+
+  fn tags(yaml: &str) -> Vec<String> {
+    let metadata: Metadata = serde_yaml::from_str(yaml).unwrap();
+    metadata.tags.unwrap_or_default().0
+  }
+
+  #[test]
+  fn test_tags() {
+    let expected = vec![String::from("database"), String::from("book")];
+    assert_eq!(tags("title: t\ntags: database, book"), expected);
+    assert_eq!(tags("title: t\ntags: database,book,"), expected);
+    assert_eq!(tags("title: t\ntags: database、 book"), expected);
+    assert_eq!(tags("title: t\ntags: [database, book, database]"), expected);
+    assert_eq!(tags("title: t\ntags:\n  - database\n  - ' book '\n"), expected);
+    assert_eq!(tags("title: t\ntags: [2020, rust]"), vec![String::from("2020"), String::from("rust")]);
+    assert_eq!(tags("title: t\ntags:\n"), Vec::<String>::new());
+    assert_eq!(tags("title: t\ntags: [\"Hello, world\", rust]"), vec![String::from("Hello, world"), String::from("rust")]);
+    let error = |yaml| serde_yaml::from_str::<Metadata>(yaml).err().unwrap().to_string();
+    assert!(error("title: t\ntags: {a: 1}\n").starts_with("expected terms but got a mapping"));
+    assert!(error("title: t\ntags: [a, [b]]\n").starts_with("expected a term but got a list"));
+  }
+
+  #[test]
+  fn test_take_taxonomies() {
+    let mut metadata: Metadata = serde_yaml::from_str("title: t\ntags: rust\ncategories: [Programming]\nseries: hakyll、zola\nauthor: utky\n").unwrap();
+    metadata.take_taxonomies(&[String::from("categories"), String::from("series"), String::from("missing")]).unwrap();
+    let parsed = front_matter(&metadata);
+    assert_eq!(parsed["taxonomies"]["tags"][0].as_str(), Some("rust"));
+    assert_eq!(parsed["taxonomies"]["categories"][0].as_str(), Some("Programming"));
+    assert_eq!(parsed["taxonomies"]["series"][1].as_str(), Some("zola"));
+    assert_eq!(parsed["extra"]["author"].as_str(), Some("utky"));
+    assert!(parsed["extra"].get("categories").is_none());
+    let names: BTreeSet<String> = metadata.taxonomy_names();
+    assert_eq!(taxonomies_config(&names), "[[taxonomies]]\nname = \"categories\"\n\n[[taxonomies]]\nname = \"series\"\n\n[[taxonomies]]\nname = \"tags\"\n");
+  }
+
+  #[test]
+  fn test_merge_sidecar() {
+    let sidecar = parse_mapping("title: sidecar\ntags: rust\nauthor: utky\n").unwrap();
+    let metadata = merge_sidecar(sidecar, Some(parse_mapping("\ntitle: inline\n").unwrap())).unwrap();
+    assert_eq!(metadata.title, "inline");
+    assert_eq!(metadata.tags, Some(Terms(vec![String::from("rust")])));
+    assert_eq!(metadata.extra["author"], serde_yaml::Value::String(String::from("utky")));
+
+    let metadata = merge_sidecar(parse_mapping("title: sidecar\n").unwrap(), None).unwrap();
+    assert_eq!(metadata.title, "sidecar");
+    assert!(merge_sidecar(parse_mapping("").unwrap(), None).is_err());
+    assert!(parse_mapping("- a\n- b\n").is_err());
+  }
+
+  #[test]
+  fn test_from_body() {
+    assert_eq!(Metadata::from_body("intro\n\n# Hello, zola #\n\n# second\n", "hello").title, "Hello, zola");
+    assert_eq!(Metadata::from_body("## not a title\nbody\n", "big-data_ingestion").title, "big data ingestion");
+  }
+}
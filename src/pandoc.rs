@@ -0,0 +1,654 @@
+//! Pandoc's extensions to markdown that hakyll posts lean on but zola's commonmark renderer
+//! doesn't know, rewritten as commonmark or html. Fenced code is left alone.
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Range;
+use std::sync::OnceLock;
+use regex::{Captures, Regex};
+use crate::markdown::map_text_blocks;
+
+pub const GRID_TABLE: &str = "grid table";
+pub const SIMPLE_TABLE: &str = "simple table";
+pub const DEFINITION_LIST: &str = "definition list";
+pub const SUPERSCRIPT: &str = "superscript";
+pub const SUBSCRIPT: &str = "subscript";
+pub const BRACKETED_SPAN: &str = "bracketed span";
+pub const FENCED_DIV: &str = "fenced div";
+pub const LINE_BLOCK: &str = "line block";
+pub const EXAMPLE_LIST: &str = "example list";
+
+/// How many of each construct a post had rewritten, by the names above.
+pub type Touched = BTreeMap<&'static str, usize>;
+
+/// Rewrite the pandoc constructs in `body`.
+pub fn convert(body: &str) -> (String, Touched) {
+  let mut converter = Converter { touched: Touched::new(), examples: example_labels(body), next_example: 0, divs: 0 };
+  let converted = map_text_blocks(body, |text| converter.blocks(text));
+  (converted, converter.touched)
+}
+
+/// `rewrote pandoc markdown: grid table (1), superscript (2)`, for the warnings of a post.
+pub fn describe(touched: &Touched) -> String {
+  let constructs: Vec<String> = touched.iter().map(|(name, count)| format!("{} ({})", name, count)).collect();
+  format!("rewrote pandoc markdown: {}", constructs.join(", "))
+}
+
+struct Converter {
+  touched: Touched,
+  /// Numbers of the labelled example list items, which references to them turn into.
+  examples: HashMap<String, usize>,
+  /// Examples numbered so far.
+  next_example: usize,
+  /// Fenced divs open, which may span fenced code.
+  divs: usize,
+}
+
+impl Converter {
+  fn touch(&mut self, construct: &'static str) {
+    *self.touched.entry(construct).or_default() += 1;
+  }
+
+  fn blocks(&mut self, text: &str) -> String {
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    let mut out = String::with_capacity(text.len());
+    let mut indented_code = false;
+    let mut idx = 0;
+    while idx < lines.len() {
+      let line = lines[idx].trim_end_matches(['\n', '\r']);
+      let previous_blank = idx == 0 || lines[idx - 1].trim().is_empty();
+      if let Some(attributes) = div_open(line) {
+        self.touch(FENCED_DIV);
+        self.divs += 1;
+        out.push_str(&format!("<div{}>\n\n", attributes));
+        idx += 1;
+      }
+      else if self.divs > 0 && is_div_close(line) {
+        self.divs -= 1;
+        out.push_str("\n</div>\n");
+        idx += 1;
+      }
+      else if is_grid_border(line) {
+        let end = (idx..lines.len()).find(|&i| !lines[i].trim_start().starts_with(['+', '|'])).unwrap_or(lines.len());
+        self.touch(GRID_TABLE);
+        out.push_str(&grid_table(&lines[idx..end]));
+        idx = end;
+      }
+      else if previous_blank && !line.trim().is_empty() && lines.get(idx + 1).is_some_and(|next| dash_groups(next).len() > 1) {
+        let end = (idx + 2..lines.len()).find(|&i| lines[i].trim().is_empty() || !dash_groups(lines[i]).is_empty()).unwrap_or(lines.len());
+        self.touch(SIMPLE_TABLE);
+        out.push_str(&simple_table(&lines[idx..end]));
+        // a line of dashes may close the table too, and goes with it
+        idx = if lines.get(end).is_some_and(|line| !dash_groups(line).is_empty()) { end + 1 } else { end };
+      }
+      else if previous_blank && is_term(line) && definition_follows(&lines[idx + 1..]) {
+        self.touch(DEFINITION_LIST);
+        let (html, end) = definition_list(&lines, idx);
+        out.push_str(&html);
+        idx = end;
+      }
+      else if is_line_block(line) {
+        let mut end = idx + 1;
+        while end < lines.len() && (is_line_block(lines[end]) || lines[end].starts_with(' ') && !lines[end].trim().is_empty()) {
+          end += 1;
+        }
+        if lines[idx..end].iter().any(|line| is_table_delimiter(line)) {
+          // a pipe table, which zola does know
+          lines[idx..end].iter().for_each(|line| out.push_str(line));
+        }
+        else {
+          self.touch(LINE_BLOCK);
+          out.push_str(&line_block(&lines[idx..end]));
+        }
+        idx = end;
+      }
+      else {
+        indented_code = (indented_code || previous_blank) && (line.starts_with("    ") || line.starts_with('\t'));
+        if indented_code {
+          out.push_str(lines[idx]);
+        }
+        else {
+          let line = self.example_item(lines[idx]);
+          out.push_str(&self.inlines(&line));
+        }
+        idx += 1;
+      }
+    }
+    out
+  }
+
+  /// `(@)` and `(@label)` items numbered on from the previous example list.
+  fn example_item(&mut self, line: &str) -> String {
+    let pattern = example_item_pattern();
+    if !pattern.is_match(line) {
+      return line.to_string();
+    }
+    self.touch(EXAMPLE_LIST);
+    self.next_example += 1;
+    let number = self.next_example;
+    pattern.replace(line, |caps: &Captures| format!("{}{}.{}", &caps[1], number, &caps[3])).into_owned()
+  }
+
+  fn inlines(&mut self, line: &str) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut start = 0;
+    while let Some(kept) = find_verbatim(line, start) {
+      out.push_str(&self.inline_text(&line[start..kept.start]));
+      out.push_str(&line[kept.clone()]);
+      start = kept.end;
+    }
+    out.push_str(&self.inline_text(&line[start..]));
+    out
+  }
+
+  /// Inline constructs of text with no code, math, tags or urls in it.
+  fn inline_text(&mut self, segment: &str) -> String {
+    let [span, sup, sub, reference] = inline_patterns();
+    let mut touched = Vec::new();
+    let segment = span.replace_all(segment, |caps: &Captures| {
+      touched.push(BRACKETED_SPAN);
+      format!("<span{}>{}</span>", attributes(&caps[2]), &caps[1])
+    });
+    let segment = sup.replace_all(&segment, |caps: &Captures| {
+      touched.push(SUPERSCRIPT);
+      format!("<sup>{}</sup>", &caps[1])
+    });
+    let (segment, subscripts) = subscripts(&segment, sub);
+    touched.extend(std::iter::repeat_n(SUBSCRIPT, subscripts));
+    let segment = reference.replace_all(&segment, |caps: &Captures| match self.examples.get(&caps[1]) {
+      Some(number) => number.to_string(),
+      None => caps[0].to_string(),
+    });
+    touched.into_iter().for_each(|construct| self.touch(construct));
+    segment.into_owned()
+  }
+}
+
+/// Where in `line`, from `start` on, the first piece to keep as it is goes: code, math, html
+/// tags and urls, where `^` and `~` aren't markup.
+fn find_verbatim(line: &str, start: usize) -> Option<Range<usize>> {
+  let mut from = start;
+  while let Some(found) = verbatim_pattern().find_at(line, from) {
+    // `$5 and $10` is money, not math
+    let money = found.as_str().starts_with('$') && !found.as_str().starts_with("$$")
+      && line[found.end()..].starts_with(|c: char| c.is_ascii_digit());
+    if !money {
+      return Some(found.range());
+    }
+    from = found.start() + 1;
+  }
+  None
+}
+
+fn verbatim_pattern() -> &'static Regex {
+  static PATTERN: OnceLock<Regex> = OnceLock::new();
+  PATTERN.get_or_init(|| Regex::new(concat!(
+    r"`[^`]*(`|$)",
+    r"|\]\([^)]*\)",
+    r"|^ {0,3}\[[^\]]+\]:\s*\S+",
+    r"|</?[a-zA-Z][^>]*>",
+    r"|\$\$.*?\$\$",
+    r"|\$[^$\s](?:[^$]*[^$\s])?\$",
+  )).unwrap())
+}
+
+fn example_item_pattern() -> &'static Regex {
+  static PATTERN: OnceLock<Regex> = OnceLock::new();
+  PATTERN.get_or_init(|| Regex::new(r"^(\s*)\(@([\w-]*)\)(\s+)").unwrap())
+}
+
+/// Bracketed spans, superscripts, subscripts and references to examples.
+fn inline_patterns() -> &'static [Regex; 4] {
+  static PATTERNS: OnceLock<[Regex; 4]> = OnceLock::new();
+  PATTERNS.get_or_init(|| [
+    Regex::new(r"\[([^\[\]]*)\]\{([^}]*)\}").unwrap(),
+    // not across spaces, nor footnotes like `[^1]`
+    Regex::new(r"\^([^\s^\[\]]+)\^").unwrap(),
+    // not across spaces, nor paths like `/~user/`
+    Regex::new(r"~([^\s~\[\]/]+)~").unwrap(),
+    Regex::new(r"\(@([\w-]+)\)").unwrap(),
+  ])
+}
+
+/// `text` with the matches of `pattern` that aren't part of `~~strikeout~~`, which zola does
+/// know, nor escaped turned into subscripts, and how many there were. Each match is checked
+/// on its own so that `C~6~H~12~O~6~` has all three.
+fn subscripts(text: &str, pattern: &Regex) -> (String, usize) {
+  let mut out = String::with_capacity(text.len());
+  let mut count = 0;
+  let (mut copied, mut from) = (0, 0);
+  while let Some(caps) = pattern.captures_at(text, from) {
+    let found = caps.get(0).unwrap();
+    let before = text[..found.start()].chars().next_back();
+    if matches!(before, Some('~') | Some('\\')) || text[found.end()..].starts_with('~') {
+      from = found.start() + 1;
+      continue;
+    }
+    out.push_str(&text[copied..found.start()]);
+    out.push_str(&format!("<sub>{}</sub>", &caps[1]));
+    count += 1;
+    copied = found.end();
+    from = found.end();
+  }
+  out.push_str(&text[copied..]);
+  (out, count)
+}
+
+/// Numbers of the labelled examples in the order pandoc numbers all examples, so that
+/// references can come before the example.
+fn example_labels(body: &str) -> HashMap<String, usize> {
+  let mut labels = HashMap::new();
+  let mut number = 0;
+  map_text_blocks(body, |text| {
+    for line in text.lines() {
+      if let Some(caps) = example_item_pattern().captures(line) {
+        number += 1;
+        if !caps[2].is_empty() {
+          labels.insert(caps[2].to_string(), number);
+        }
+      }
+    }
+    String::new()
+  });
+  labels
+}
+
+/// ` class="a b" id="x" key="value"` for pandoc's `{.a .b #x key=value}`, or `a` alone.
+fn attributes(text: &str) -> String {
+  let text = text.trim();
+  let text = text.strip_prefix('{').and_then(|text| text.strip_suffix('}')).unwrap_or(text);
+  let mut classes = Vec::new();
+  let mut rest = Vec::new();
+  for word in text.split_whitespace() {
+    if let Some(class) = word.strip_prefix('.') {
+      classes.push(class);
+    }
+    else if let Some(id) = word.strip_prefix('#') {
+      rest.push(format!(" id=\"{}\"", id));
+    }
+    else if let Some((key, value)) = word.split_once('=') {
+      rest.push(format!(" {}=\"{}\"", key, value.trim_matches('"')));
+    }
+    else {
+      classes.push(word);
+    }
+  }
+  let mut attributes = String::new();
+  if !classes.is_empty() {
+    attributes.push_str(&format!(" class=\"{}\"", classes.join(" ")));
+  }
+  attributes.extend(rest);
+  attributes
+}
+
+/// Attributes of `::: note` or `::: {.note}`.
+fn div_open(line: &str) -> Option<String> {
+  let rest = line.strip_prefix(":::")?.trim_start_matches(':').trim().trim_end_matches(':').trim();
+  if rest.is_empty() {
+    None
+  }
+  else if rest.starts_with('{') || !rest.contains(char::is_whitespace) {
+    Some(attributes(rest))
+  }
+  else {
+    None
+  }
+}
+
+fn is_div_close(line: &str) -> bool {
+  line.starts_with(":::") && line.trim().chars().all(|c| c == ':')
+}
+
+fn is_grid_border(line: &str) -> bool {
+  let line = line.trim();
+  line.len() > 2 && line.starts_with('+') && line.ends_with('+') && line.chars().all(|c| "+-=:".contains(c))
+}
+
+/// Grid table rows, separated by border lines, as a pipe table. Cells spanning several lines
+/// are joined into one.
+fn grid_table(lines: &[&str]) -> String {
+  let mut header = None;
+  let mut rows: Vec<Vec<String>> = Vec::new();
+  let mut cells: Vec<Vec<String>> = Vec::new();
+  let mut alignments = Vec::new();
+  for line in lines.iter().map(|line| line.trim()) {
+    if line.starts_with('+') {
+      if alignments.is_empty() || line.contains('=') {
+        // the header border tells the alignment when there is one
+        alignments = line.trim_matches('+').split('+')
+          .map(|segment| alignment(segment.starts_with(':'), segment.ends_with(':')))
+          .collect();
+      }
+      if !cells.is_empty() {
+        rows.push(cells.drain(..).map(|cell| cell.join(" ")).collect());
+      }
+      if line.contains('=') {
+        header = Some(merge_rows(rows.drain(..)));
+      }
+    }
+    else {
+      let row: Vec<&str> = line.trim_matches('|').split('|').collect();
+      if cells.len() < row.len() {
+        cells.resize(row.len(), Vec::new());
+      }
+      for (cell, text) in cells.iter_mut().zip(row) {
+        if !text.trim().is_empty() {
+          cell.push(text.trim().to_string());
+        }
+      }
+    }
+  }
+  if !cells.is_empty() {
+    rows.push(cells.drain(..).map(|cell| cell.join(" ")).collect());
+  }
+  // commonmark tables can't do without a header
+  let header = header.unwrap_or_else(|| vec![String::new(); alignments.len()]);
+  pipe_table(header, &alignments, rows)
+}
+
+/// Header rows joined column by column.
+fn merge_rows(rows: impl Iterator<Item = Vec<String>>) -> Vec<String> {
+  let mut merged: Vec<String> = Vec::new();
+  for row in rows {
+    if merged.len() < row.len() {
+      merged.resize(row.len(), String::new());
+    }
+    for (cell, text) in merged.iter_mut().zip(row) {
+      if !cell.is_empty() && !text.is_empty() {
+        cell.push(' ');
+      }
+      cell.push_str(&text);
+    }
+  }
+  merged
+}
+
+/// The delimiter row cell of a pipe table.
+fn alignment(left: bool, right: bool) -> &'static str {
+  match (left, right) {
+    (true, true) => ":---:",
+    (true, false) => ":---",
+    (false, true) => "---:",
+    (false, false) => "---",
+  }
+}
+
+fn pipe_table(header: Vec<String>, alignments: &[&str], rows: Vec<Vec<String>>) -> String {
+  let columns = alignments.len().max(header.len());
+  let row_line = |row: &[String]| {
+    let mut cells: Vec<&str> = row.iter().map(|cell| cell.as_str()).collect();
+    cells.resize(columns, "");
+    format!("| {} |\n", cells.join(" | "))
+  };
+  let mut table = row_line(&header);
+  let mut delimiters: Vec<&str> = alignments.to_vec();
+  delimiters.resize(columns, "---");
+  table.push_str(&format!("| {} |\n", delimiters.join(" | ")));
+  for row in &rows {
+    table.push_str(&row_line(row));
+  }
+  table
+}
+
+/// Char ranges of the runs of dashes in the line under the header of a simple table.
+fn dash_groups(line: &str) -> Vec<(usize, usize)> {
+  let line = line.trim_end_matches(['\n', '\r']);
+  if !line.contains('-') || !line.chars().all(|c| c == '-' || c == ' ') {
+    return Vec::new();
+  }
+  let mut groups = Vec::new();
+  let mut start = None;
+  for (idx, c) in line.chars().chain(std::iter::once(' ')).enumerate() {
+    match (c, start) {
+      ('-', None) => start = Some(idx),
+      (' ', Some(from)) => {
+        groups.push((from, idx));
+        start = None;
+      },
+      _ => {},
+    }
+  }
+  // `- - -` is a thematic break, not a table
+  if groups.iter().any(|(from, to)| to - from < 3) {
+    return Vec::new();
+  }
+  groups
+}
+
+/// A header line, the dashed line under it and the rows down to the next blank line or line
+/// of dashes, which are cut into cells where the dashes of each column end.
+fn simple_table(lines: &[&str]) -> String {
+  let groups = dash_groups(lines[1]);
+  let cut = |line: &str| -> Vec<String> {
+    let chars: Vec<char> = line.trim_end_matches(['\n', '\r']).chars().collect();
+    let mut from = 0;
+    groups.iter().enumerate().map(|(idx, &(_, end))| {
+      let to = if idx + 1 == groups.len() { chars.len() } else { end.min(chars.len()) };
+      let cell: String = chars[from.min(to)..to].iter().collect();
+      from = to;
+      cell.trim().to_string()
+    }).collect()
+  };
+  let header_line: Vec<char> = lines[0].trim_end_matches(['\n', '\r']).chars().collect();
+  let alignments: Vec<&str> = groups.iter().map(|&(start, end)| {
+    let flush_left = header_line.get(start).is_some_and(|c| !c.is_whitespace());
+    let flush_right = end > 0 && header_line.get(end - 1).is_some_and(|c| !c.is_whitespace());
+    match (flush_left, flush_right) {
+      (true, false) => alignment(true, false),
+      (false, true) => alignment(false, true),
+      (false, false) => alignment(true, true),
+      (true, true) => alignment(false, false),
+    }
+  }).collect();
+  let rows = lines[2..].iter().map(|line| cut(line)).collect();
+  let mut table = pipe_table(cut(lines[0]), &alignments, rows);
+  if lines.last().is_some_and(|line| line.ends_with('\n')) {
+    table
+  }
+  else {
+    table.pop();
+    table
+  }
+}
+
+fn is_term(line: &str) -> bool {
+  !line.trim().is_empty() && !line.starts_with([' ', '\t', '#', '>', '|', ':', '~', '-', '*', '+'])
+}
+
+fn definition_marker(line: &str) -> Option<&str> {
+  let rest = line.strip_prefix([':', '~'])?;
+  let text = rest.trim_start_matches([' ', '\t']);
+  if text.len() < rest.len() && rest.len() - text.len() <= 4 && !text.is_empty() {
+    Some(text)
+  }
+  else {
+    None
+  }
+}
+
+/// Whether the lines after a term start with its definition, maybe after a blank line.
+fn definition_follows(lines: &[&str]) -> bool {
+  match lines {
+    [next, ..] if definition_marker(next).is_some() => true,
+    [blank, next, ..] => blank.trim().is_empty() && definition_marker(next).is_some(),
+    _ => false,
+  }
+}
+
+/// The definition list starting at the term on `lines[start]`, as html around the
+/// definitions, which stay markdown. Also returns the line after the list.
+fn definition_list(lines: &[&str], start: usize) -> (String, usize) {
+  let mut html = String::from("<dl>\n");
+  let mut idx = start;
+  while idx < lines.len() && is_term(lines[idx]) && definition_follows(&lines[idx + 1..]) {
+    html.push_str(&format!("<dt>{}</dt>\n", lines[idx].trim()));
+    idx += 1;
+    loop {
+      let next = (idx..lines.len()).find(|&i| !lines[i].trim().is_empty()).unwrap_or(lines.len());
+      let first = match lines.get(next).and_then(|line| definition_marker(line)) {
+        Some(first) => first,
+        None => break,
+      };
+      idx = next;
+      html.push_str("<dd>\n\n");
+      html.push_str(first.trim_end());
+      html.push('\n');
+      idx += 1;
+      while idx < lines.len() {
+        let line = lines[idx];
+        let indented = line.starts_with("    ") || line.starts_with('\t');
+        if line.trim().is_empty() {
+          // a blank line ends the definition unless what follows is indented into it
+          match lines.get(idx + 1) {
+            Some(next) if next.starts_with("    ") || next.starts_with('\t') => html.push('\n'),
+            _ => break,
+          }
+        }
+        else if indented || definition_marker(line).is_none() {
+          let dedented = line.strip_prefix("    ").or_else(|| line.strip_prefix('\t')).unwrap_or(line);
+          html.push_str(dedented.trim_end_matches(['\n', '\r']));
+          html.push('\n');
+        }
+        else {
+          break;
+        }
+        idx += 1;
+      }
+      html.push_str("\n</dd>\n");
+    }
+    while idx < lines.len() && lines[idx].trim().is_empty() && lines.get(idx + 1).is_some_and(|next| is_term(next)) && definition_follows(&lines[idx + 2..]) {
+      idx += 1;
+    }
+  }
+  html.push_str("</dl>\n");
+  (html, idx)
+}
+
+/// `| text`, but not a row of a pipe table.
+fn is_line_block(line: &str) -> bool {
+  let line = line.trim_end_matches(['\n', '\r']);
+  (line == "|" || line.starts_with("| ")) && !line.trim_end().ends_with('|')
+}
+
+fn is_table_delimiter(line: &str) -> bool {
+  let line = line.trim();
+  line.contains('-') && line.chars().all(|c| "|-: ".contains(c))
+}
+
+/// Lines kept apart with `<br>`, and their leading spaces kept too.
+fn line_block(lines: &[&str]) -> String {
+  let mut kept: Vec<String> = Vec::new();
+  for line in lines {
+    let line = line.trim_end_matches(['\n', '\r']);
+    match line.strip_prefix('|') {
+      Some(text) => {
+        let text = text.strip_prefix(' ').unwrap_or(text);
+        let content = text.trim_start_matches(' ');
+        kept.push(format!("{}{}", "&nbsp;".repeat(text.len() - content.len()), content));
+      },
+      // a continuation of the line before
+      None => {
+        if let Some(last) = kept.last_mut() {
+          last.push(' ');
+          last.push_str(line.trim());
+        }
+      },
+    }
+  }
+  let mut block = kept.join("<br>\n");
+  block.push('\n');
+  block
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{convert, describe};
+
+  #[test]
+  fn test_grid_table() {
+    let body = "\
+      +-------+---------+\n\
+      | Fruit | Price   |\n\
+      +=======+========:+\n\
+      | Apple | 1.00    |\n\
+      | red   |         |\n\
+      +-------+---------+\n";
+    let (converted, touched) = convert(body);
+    assert_eq!(converted, "| Fruit | Price |\n| --- | ---: |\n| Apple red | 1.00 |\n");
+    assert_eq!(describe(&touched), "rewrote pandoc markdown: grid table (1)");
+  }
+
+  #[test]
+  fn test_simple_table() {
+    let body = "\
+      \x20 Right Left     Center   Default\n\
+      ------- ------ ---------- -------\n\
+      \x20    12 12         12    12\n\
+      \n\
+      after\n";
+    let (converted, _) = convert(body);
+    assert_eq!(converted, "\
+      | Right | Left | Center | Default |\n\
+      | ---: | :--- | :---: | --- |\n\
+      | 12 | 12 | 12 | 12 |\n\
+      \n\
+      after\n");
+  }
+
+  #[test]
+  fn test_simple_table_closed() {
+    let (converted, _) = convert("\nName  Age\n----- ---\nAnn    31\n----- ---\n\nafter\n");
+    assert_eq!(converted, "\n| Name | Age |\n| :--- | --- |\n| Ann | 31 |\n\nafter\n");
+  }
+
+  #[test]
+  fn test_definition_list() {
+    let body = "Term\n:   Definition\n    more *of it*\n\nOther\n\n~ Second\n\nafter\n";
+    let (converted, touched) = convert(body);
+    assert_eq!(converted, "\
+      <dl>\n<dt>Term</dt>\n<dd>\n\nDefinition\nmore *of it*\n\n</dd>\n\
+      <dt>Other</dt>\n<dd>\n\nSecond\n\n</dd>\n</dl>\n\
+      \n\
+      after\n");
+    assert_eq!(touched.len(), 1);
+  }
+
+  #[test]
+  fn test_inlines() {
+    let (converted, touched) = convert("H~2~O and 2^10^, [small]{.smallcaps #s}, ~~gone~~ `a~b~`, [^1][^2]\n");
+    assert_eq!(converted, "H<sub>2</sub>O and 2<sup>10</sup>, <span class=\"smallcaps\" id=\"s\">small</span>, ~~gone~~ `a~b~`, [^1][^2]\n");
+    assert_eq!(describe(&touched), "rewrote pandoc markdown: bracketed span (1), subscript (1), superscript (1)");
+  }
+
+  #[test]
+  fn test_subscripts() {
+    let (converted, touched) = convert("C~6~H~12~O~6~ and ~~gone~~ and \\~x~ and a~~b~~\n");
+    assert_eq!(converted, "C<sub>6</sub>H<sub>12</sub>O<sub>6</sub> and ~~gone~~ and \\~x~ and a~~b~~\n");
+    assert_eq!(describe(&touched), "rewrote pandoc markdown: subscript (3)");
+  }
+
+  #[test]
+  fn test_inlines_verbatim() {
+    let body = "[x](http://e.com/a~b~c) <img src=\"a^b^.png\"> $a^b$,$c^d$ $$x^2^$$ <http://e.com/~u~>\n[y]: /a^b^\n$5 and $10^2^\n";
+    let (converted, touched) = convert(body);
+    assert_eq!(converted, "[x](http://e.com/a~b~c) <img src=\"a^b^.png\"> $a^b$,$c^d$ $$x^2^$$ <http://e.com/~u~>\n[y]: /a^b^\n$5 and $10<sup>2</sup>\n");
+    assert_eq!(describe(&touched), "rewrote pandoc markdown: superscript (1)");
+  }
+
+  #[test]
+  fn test_fenced_div() {
+    let (converted, _) = convert("::: {.warning #w}\nbe *careful*\n:::\n::: note\n```\n:::\n```\n:::\n\n    x^2^\n");
+    assert_eq!(converted, "<div class=\"warning\" id=\"w\">\n\nbe *careful*\n\n</div>\n<div class=\"note\">\n\n```\n:::\n```\n\n</div>\n\n    x^2^\n");
+  }
+
+  #[test]
+  fn test_line_block() {
+    let (converted, _) = convert("| The limerick packs\n|    laughs anatomical\n  in space\n\n| a | b |\n|---|---|\n| 1 | 2 |\n");
+    assert_eq!(converted, "The limerick packs<br>\n&nbsp;&nbsp;&nbsp;laughs anatomical in space\n\n| a | b |\n|---|---|\n| 1 | 2 |\n");
+  }
+
+  #[test]
+  fn test_example_list() {
+    let (converted, touched) = convert("as (@good) shows\n\n(@) one\n(@good) two\n\n```\n(@) code\n```\n(@) three\n");
+    assert_eq!(converted, "as 2 shows\n\n1. one\n2. two\n\n```\n(@) code\n```\n3. three\n");
+    assert_eq!(touched.get("example list"), Some(&3));
+  }
+}
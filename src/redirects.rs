@@ -0,0 +1,90 @@
+//! Server side redirects from the old urls, which search engines take better than the
+//! meta refresh pages zola generates for `aliases`.
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Format {
+  /// `_redirects` as read by netlify and cloudflare pages.
+  Netlify,
+  /// `rewrite` directives to include into a `server` block.
+  Nginx,
+  /// `Redirect 301` directives for `.htaccess`.
+  Apache,
+  Csv,
+}
+
+pub const FORMATS: [&str; 4] = ["netlify", "nginx", "apache", "csv"];
+
+impl std::str::FromStr for Format {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Format, String> {
+    match s {
+      "netlify" => Ok(Format::Netlify),
+      "nginx" => Ok(Format::Nginx),
+      "apache" => Ok(Format::Apache),
+      "csv" => Ok(Format::Csv),
+      _ => Err(format!("unknown redirect format {:?}, expected one of {:?}", s, FORMATS)),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Redirect {
+  pub from: String,
+  pub to: String,
+}
+
+pub fn render(redirects: &[Redirect], format: Format) -> String {
+  let mut buf = String::new();
+  if format == Format::Csv {
+    buf.push_str("from,to\n");
+  }
+  for Redirect { from, to } in redirects {
+    let line = match format {
+      Format::Netlify => format!("{} {} 301", from, to),
+      Format::Nginx => format!("rewrite ^{}$ {} permanent;", regex::escape(from), to),
+      Format::Apache => format!("Redirect 301 {} {}", quote_if_spaced(from), quote_if_spaced(to)),
+      Format::Csv => format!("{},{}", csv_field(from), csv_field(to)),
+    };
+    buf.push_str(&line);
+    buf.push('\n');
+  }
+  buf
+}
+
+fn quote_if_spaced(url: &str) -> String {
+  if url.contains(' ') { format!("\"{}\"", url) } else { url.to_string() }
+}
+
+fn csv_field(field: &str) -> String {
+  if field.contains([',', '"', '\n']) {
+    format!("\"{}\"", field.replace('"', "\"\""))
+  }
+  else {
+    field.to_string()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{render, Format, Redirect};
+
+  fn redirects() -> Vec<Redirect> {
+    vec![
+      Redirect { from: String::from("/posts/2020-02-01-foo.html"), to: String::from("/posts/foo/") },
+      Redirect { from: String::from("/posts/a,b.html"), to: String::from("/posts/a-b/") },
+    ]
+  }
+
+  #[test]
+  fn test_render() {
+    assert_eq!(render(&redirects(), Format::Netlify),
+               "/posts/2020-02-01-foo.html /posts/foo/ 301\n/posts/a,b.html /posts/a-b/ 301\n");
+    assert_eq!(render(&redirects(), Format::Nginx),
+               "rewrite ^/posts/2020\\-02\\-01\\-foo\\.html$ /posts/foo/ permanent;\nrewrite ^/posts/a,b\\.html$ /posts/a-b/ permanent;\n");
+    assert_eq!(render(&redirects(), Format::Apache),
+               "Redirect 301 /posts/2020-02-01-foo.html /posts/foo/\nRedirect 301 /posts/a,b.html /posts/a-b/\n");
+    assert_eq!(render(&redirects(), Format::Csv),
+               "from,to\n/posts/2020-02-01-foo.html,/posts/foo/\n\"/posts/a,b.html\",/posts/a-b/\n");
+  }
+}
@@ -0,0 +1,94 @@
+//! Zola sections for the directories posts are converted into, without which zola has
+//! nothing to list the posts on.
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use serde::Serialize;
+
+/// What the user chose about the generated sections.
+#[derive(Debug, Default)]
+pub struct SectionOptions {
+  pub paginate_by: Option<usize>,
+  pub template: Option<String>,
+}
+
+#[derive(Serialize)]
+struct SectionFrontMatter<'a> {
+  title: &'a str,
+  sort_by: &'a str,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  paginate_by: Option<usize>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  template: Option<&'a str>,
+}
+
+/// The directories under `content_root` that `pages` are in, along with those between them
+/// and `content_root`. The content directory itself is left out, since zola renders the
+/// home page without an `_index.md`.
+pub fn section_dirs<'a>(pages: impl Iterator<Item = &'a Path>, content_root: &Path) -> BTreeSet<PathBuf> {
+  let mut dirs = BTreeSet::new();
+  for page in pages {
+    let mut dir = page.parent();
+    if page.file_name().is_some_and(|name| name == "index.md") {
+      // the directory of a page bundle is the page, not a section
+      dir = dir.and_then(|dir| dir.parent());
+    }
+    while let Some(section) = dir.filter(|dir| dir.starts_with(content_root) && *dir != content_root) {
+      dirs.insert(section.to_path_buf());
+      dir = section.parent();
+    }
+  }
+  dirs
+}
+
+/// `_index.md` for the section at `dir`, titled after the directory.
+pub fn format_section(dir: &Path, options: &SectionOptions) -> Result<String, toml::ser::Error> {
+  let name = dir.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+  let front_matter = SectionFrontMatter {
+    title: &title_of(&name),
+    sort_by: "date",
+    paginate_by: options.paginate_by,
+    template: options.template.as_deref(),
+  };
+  let mut buf = String::new();
+  buf.push_str("+++\n");
+  front_matter.serialize(toml::Serializer::new(&mut buf).pretty_string(true).pretty_string_literal(false))?;
+  buf.push_str("+++\n");
+  Ok(buf)
+}
+
+/// `Release notes` for `release-notes`.
+fn title_of(name: &str) -> String {
+  let words = name.replace(['-', '_'], " ");
+  let mut chars = words.chars();
+  match chars.next() {
+    Some(first) => first.to_uppercase().chain(chars).collect(),
+    None => words,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::{Path, PathBuf};
+  use super::{format_section, section_dirs, SectionOptions};
+
+  #[test]
+  fn test_section_dirs() {
+    let pages = [
+      Path::new("content/blog/posts/foo.md"),
+      Path::new("content/blog/posts/bar/index.md"),
+      Path::new("content/notes/baz.md"),
+      Path::new("content/about.md"),
+    ];
+    let dirs: Vec<PathBuf> = section_dirs(pages.iter().copied(), Path::new("content")).into_iter().collect();
+    assert_eq!(dirs, vec![PathBuf::from("content/blog"), PathBuf::from("content/blog/posts"), PathBuf::from("content/notes")]);
+  }
+
+  #[test]
+  fn test_format_section() {
+    assert_eq!(format_section(Path::new("content/release-notes"), &SectionOptions::default()).unwrap(),
+               "+++\ntitle = \"Release notes\"\nsort_by = \"date\"\n+++\n");
+    let options = SectionOptions { paginate_by: Some(10), template: Some(String::from("blog.html")) };
+    assert_eq!(format_section(Path::new("content/posts"), &options).unwrap(),
+               "+++\ntitle = \"Posts\"\nsort_by = \"date\"\npaginate_by = 10\ntemplate = \"blog.html\"\n+++\n");
+  }
+}
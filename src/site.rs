@@ -0,0 +1,578 @@
+//! Just enough of hakyll's `site.hs` to know where the old site put things, read statically
+//! since running the haskell is out of the question.
+use std::collections::BTreeMap;
+use regex::Regex;
+
+/// What `teaserField` cuts posts at.
+pub const TEASER_SEPARATOR: &str = "<!--more-->";
+
+/// The `match` rules of a `site.hs`, in the order hakyll tries them.
+pub struct Site {
+  pub rules: Vec<Rule>,
+  /// Record fields set to a string, like `feedTitle` of the `FeedConfiguration` or
+  /// `destinationDirectory` of the `Configuration`.
+  pub fields: BTreeMap<String, String>,
+  /// Fields the contexts of `site.hs` define, as far as they are built from hakyll's own.
+  pub context_fields: Vec<ContextField>,
+  /// Whether `buildCategories` files each post under the directory it is in.
+  pub categories: bool,
+  /// Rules that were found but can't be made sense of.
+  pub warnings: Vec<String>,
+}
+
+pub struct Rule {
+  pub patterns: Vec<String>,
+  /// `None` when the rule has no `route`, in which case hakyll writes nothing.
+  pub route: Option<Route>,
+  /// Line of `site.hs` the rule starts on, for warnings.
+  pub line: usize,
+  globs: Vec<Regex>,
+}
+
+/// A field of a hakyll `Context`, named as templates refer to it.
+#[derive(Debug, Eq, PartialEq)]
+pub enum ContextField {
+  /// `dateField "date" "%B %e, %Y"`
+  Date { name: String, format: String },
+  /// `teaserField "teaser" "content"`, or `teaserFieldWithSeparator` with a separator
+  /// other than `<!--more-->`
+  Teaser { name: String, separator: String },
+  /// `tagsField "tags" tags`, or `categoryField`
+  Tags { name: String },
+  /// `constField "author" "Jane"`
+  Const { name: String, value: String },
+}
+
+#[derive(Debug)]
+pub enum Route {
+  Id,
+  SetExtension(String),
+  Const(String),
+  Gsub(Regex, String),
+  Compose(Box<Route>, Box<Route>),
+  /// Anything else, such as `customRoute`, kept as written for the warning.
+  Unknown(String),
+}
+
+impl Site {
+  pub fn parse(source: &str) -> Site {
+    let source = strip_comments(source);
+    let lines: Vec<&str> = source.lines().collect();
+    let tokens = tokenize(&source);
+    let categories = tokens.iter().any(|token| *token == Token::Ident(String::from("buildCategories")));
+    let mut site = Site { rules: Vec::new(), fields: record_fields(&tokens), context_fields: context_fields(&tokens), categories, warnings: Vec::new() };
+    let mut idx = 0;
+    while idx < lines.len() {
+      let line = lines[idx];
+      if !has_word(line, "match") {
+        idx += 1;
+        continue;
+      }
+      let indent = indentation(line);
+      let end = (idx + 1..lines.len())
+        .find(|&i| !lines[i].trim().is_empty() && indentation(lines[i]) <= indent)
+        .unwrap_or(lines.len());
+      let head = &line[line.find("match").unwrap() + "match".len()..];
+      if has_word(head, "version") {
+        // another version of the same items, e.g. the raw markdown; not what urls point to
+        idx = end;
+        continue;
+      }
+      let patterns = string_literals(head.split(" do").next().unwrap());
+      let route = (idx + 1..end).find(|&i| has_word(lines[i], "route")).map(|i| {
+        let statement = continued_statement(&lines[..end], i);
+        let text = &statement[statement.find("route").unwrap() + "route".len()..];
+        Route::parse(text)
+      });
+      let globs: Vec<Regex> = patterns.iter().map(|pattern| glob_to_regex(pattern)).collect();
+      let rule = Rule { patterns, route, line: idx + 1, globs };
+      if let Some(Route::Unknown(text)) = rule.route.as_ref().and_then(Route::unknown) {
+        site.warnings.push(format!("site.hs:{}: can't interpret route {:?} for {:?}", rule.line, text, rule.patterns));
+      }
+      site.rules.push(rule);
+      idx = end;
+    }
+    site
+  }
+
+  pub fn field(&self, name: &str) -> Option<&str> {
+    self.fields.get(name).map(|value| value.as_str())
+  }
+
+  /// What the teaser fields cut posts at, hakyll's default first whether or not it's used.
+  pub fn teaser_separators(&self) -> Vec<String> {
+    let mut separators = vec![String::from(TEASER_SEPARATOR)];
+    for field in &self.context_fields {
+      if let ContextField::Teaser { separator, .. } = field {
+        if !separators.contains(separator) {
+          separators.push(separator.clone());
+        }
+      }
+    }
+    separators
+  }
+
+  /// Path hakyll wrote the item with `identifier`, e.g. `posts/foo.md`, to. `None` when no rule
+  /// matches it, and an error when the rule that does can't be followed.
+  pub fn route(&self, identifier: &str) -> Option<Result<String, String>> {
+    let rule = self.rules.iter().find(|rule| rule.globs.iter().any(|glob| glob.is_match(identifier)))?;
+    Some(match &rule.route {
+      Some(route) => route.apply(identifier),
+      None => Err(format!("rule at site.hs:{} for {:?} has no route", rule.line, rule.patterns)),
+    })
+  }
+}
+
+impl Route {
+  fn parse(text: &str) -> Route {
+    let tokens = tokenize(text);
+    let mut parser = Parser { tokens: &tokens, pos: 0 };
+    match parser.expr() {
+      Some(expr) if parser.pos == tokens.len() => Route::from_expr(&expr).unwrap_or_else(|| Route::Unknown(text.trim().to_string())),
+      _ => Route::Unknown(text.trim().to_string()),
+    }
+  }
+
+  fn from_expr(expr: &Expr) -> Option<Route> {
+    match expr {
+      Expr::Var(name) if name == "idRoute" => Some(Route::Id),
+      Expr::App(f, args) => match (f.as_str(), args.as_slice()) {
+        ("setExtension", [Expr::Str(ext)]) => Some(Route::SetExtension(ext.clone())),
+        ("constRoute", [Expr::Str(path)]) => Some(Route::Const(path.clone())),
+        ("gsubRoute", [Expr::Str(pattern), Expr::App(k, replacement)]) if k == "const" => match replacement.as_slice() {
+          [Expr::Str(replacement)] => Some(Route::Gsub(Regex::new(pattern).ok()?, replacement.clone())),
+          _ => None,
+        },
+        ("composeRoutes", [first, second]) => Some(Route::Compose(Box::new(Route::from_expr(first)?), Box::new(Route::from_expr(second)?))),
+        _ => None,
+      },
+      _ => None,
+    }
+  }
+
+  /// The part of this route that can't be interpreted, if any.
+  fn unknown(&self) -> Option<&Route> {
+    match self {
+      Route::Unknown(_) => Some(self),
+      Route::Compose(first, second) => first.unknown().or_else(|| second.unknown()),
+      _ => None,
+    }
+  }
+
+  pub fn apply(&self, path: &str) -> Result<String, String> {
+    match self {
+      Route::Id => Ok(path.to_string()),
+      Route::SetExtension(ext) => {
+        let ext = ext.trim_start_matches('.');
+        let file_start = path.rfind('/').map_or(0, |idx| idx + 1);
+        let stem_end = path[file_start..].rfind('.').filter(|&idx| idx > 0).map_or(path.len(), |idx| file_start + idx);
+        if ext.is_empty() {
+          Ok(path[..stem_end].to_string())
+        }
+        else {
+          Ok(format!("{}.{}", &path[..stem_end], ext))
+        }
+      },
+      Route::Const(to) => Ok(to.clone()),
+      Route::Gsub(pattern, replacement) => Ok(pattern.replace_all(path, regex::NoExpand(replacement)).into_owned()),
+      Route::Compose(first, second) => second.apply(&first.apply(path)?),
+      Route::Unknown(text) => Err(format!("can't interpret route {:?}", text)),
+    }
+  }
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+  Str(String),
+  Ident(String),
+  /// An operator, or a function used infix with backticks.
+  Op(String),
+  Open,
+  Close,
+}
+
+#[derive(Debug)]
+enum Expr {
+  Var(String),
+  Str(String),
+  App(String, Vec<Expr>),
+}
+
+/// Recursive descent over function application, `$` and infix `composeRoutes`, which is
+/// all route expressions in practice use.
+struct Parser<'a> {
+  tokens: &'a [Token],
+  pos: usize,
+}
+
+impl<'a> Parser<'a> {
+  fn expr(&mut self) -> Option<Expr> {
+    let lhs = self.app()?;
+    match self.tokens.get(self.pos) {
+      Some(Token::Op(op)) if op == "composeRoutes" => {
+        self.pos += 1;
+        Some(Expr::App(op.clone(), vec![lhs, self.expr()?]))
+      },
+      _ => Some(lhs),
+    }
+  }
+
+  fn app(&mut self) -> Option<Expr> {
+    let mut atoms = Vec::new();
+    while let Some(atom) = self.atom() {
+      atoms.push(atom?);
+    }
+    if let Some(Token::Op(op)) = self.tokens.get(self.pos) {
+      if op == "$" {
+        self.pos += 1;
+        atoms.push(self.expr()?);
+      }
+    }
+    let mut atoms = atoms.into_iter();
+    let head = atoms.next()?;
+    let args: Vec<Expr> = atoms.collect();
+    match head {
+      _ if args.is_empty() => Some(head),
+      Expr::Var(f) => Some(Expr::App(f, args)),
+      Expr::App(f, mut applied) => {
+        applied.extend(args);
+        Some(Expr::App(f, applied))
+      },
+      Expr::Str(_) => None,
+    }
+  }
+
+  /// `None` when the next token doesn't start an atom, `Some(None)` when it does but is broken.
+  fn atom(&mut self) -> Option<Option<Expr>> {
+    match self.tokens.get(self.pos)? {
+      Token::Str(s) => {
+        self.pos += 1;
+        Some(Some(Expr::Str(s.clone())))
+      },
+      Token::Ident(name) => {
+        self.pos += 1;
+        Some(Some(Expr::Var(name.clone())))
+      },
+      Token::Open => {
+        self.pos += 1;
+        let expr = self.expr();
+        if self.tokens.get(self.pos) != Some(&Token::Close) {
+          return Some(None);
+        }
+        self.pos += 1;
+        Some(expr)
+      },
+      _ => None,
+    }
+  }
+}
+
+fn tokenize(text: &str) -> Vec<Token> {
+  let mut tokens = Vec::new();
+  let mut chars = text.chars().peekable();
+  while let Some(&c) = chars.peek() {
+    if c.is_whitespace() {
+      chars.next();
+    }
+    else if c == '"' {
+      chars.next();
+      let mut s = String::new();
+      while let Some(c) = chars.next() {
+        match c {
+          '"' => break,
+          '\\' => match chars.next() {
+            Some('n') => s.push('\n'),
+            Some(escaped) => s.push(escaped),
+            None => {},
+          },
+          _ => s.push(c),
+        }
+      }
+      tokens.push(Token::Str(s));
+    }
+    else if c == '(' || c == ')' {
+      chars.next();
+      tokens.push(if c == '(' { Token::Open } else { Token::Close });
+    }
+    else if c == '`' {
+      chars.next();
+      let name: String = chars.by_ref().take_while(|&c| c != '`').collect();
+      tokens.push(Token::Op(name));
+    }
+    else if c.is_alphanumeric() || c == '_' {
+      let mut name = String::new();
+      while let Some(&c) = chars.peek() {
+        if c.is_alphanumeric() || c == '_' || c == '\'' || (c == '.' && name.starts_with(char::is_uppercase)) {
+          name.push(c);
+          chars.next();
+        }
+        else {
+          break;
+        }
+      }
+      tokens.push(Token::Ident(name));
+    }
+    else {
+      let mut op = String::new();
+      while let Some(&c) = chars.peek() {
+        if c.is_whitespace() || c.is_alphanumeric() || "\"()`_".contains(c) {
+          break;
+        }
+        op.push(c);
+        chars.next();
+      }
+      tokens.push(Token::Op(op));
+    }
+  }
+  tokens
+}
+
+/// `name = "value"` within `{ }` record syntax.
+fn record_fields(tokens: &[Token]) -> BTreeMap<String, String> {
+  let mut fields = BTreeMap::new();
+  for window in tokens.windows(4) {
+    if let [Token::Op(open), Token::Ident(name), Token::Op(eq), Token::Str(value)] = window {
+      if (open == "{" || open == ",") && eq == "=" {
+        fields.insert(name.clone(), value.clone());
+      }
+    }
+  }
+  fields
+}
+
+fn context_fields(tokens: &[Token]) -> Vec<ContextField> {
+  let mut fields = Vec::new();
+  for (idx, token) in tokens.iter().enumerate() {
+    let function = match token {
+      Token::Ident(function) => function.as_str(),
+      _ => continue,
+    };
+    let name = match tokens.get(idx + 1) {
+      Some(Token::Str(name)) => name.clone(),
+      _ => continue,
+    };
+    let second = match tokens.get(idx + 2) {
+      Some(Token::Str(second)) => Some(second.clone()),
+      _ => None,
+    };
+    let field = match (function, second) {
+      ("dateField", Some(format)) => ContextField::Date { name, format },
+      ("teaserField", _) => ContextField::Teaser { name, separator: String::from(TEASER_SEPARATOR) },
+      // the separator comes first
+      ("teaserFieldWithSeparator", Some(field)) => ContextField::Teaser { name: field, separator: name },
+      ("tagsField", _) | ("categoryField", _) => ContextField::Tags { name },
+      ("constField", Some(value)) => ContextField::Const { name, value },
+      _ => continue,
+    };
+    if !fields.contains(&field) {
+      fields.push(field);
+    }
+  }
+  fields
+}
+
+/// Blank out `--` and `{- -}` comments, keeping line breaks so line numbers stay right.
+fn strip_comments(source: &str) -> String {
+  let mut stripped = String::with_capacity(source.len());
+  let mut chars = source.chars().peekable();
+  let mut in_string = false;
+  let mut block_depth = 0;
+  while let Some(c) = chars.next() {
+    let next = chars.peek().copied();
+    if block_depth > 0 {
+      if c == '-' && next == Some('}') {
+        chars.next();
+        block_depth -= 1;
+      }
+      else if c == '{' && next == Some('-') {
+        chars.next();
+        block_depth += 1;
+      }
+      else if c == '\n' {
+        stripped.push(c);
+      }
+    }
+    else if in_string {
+      stripped.push(c);
+      if c == '\\' {
+        stripped.extend(chars.next());
+      }
+      else if c == '"' {
+        in_string = false;
+      }
+    }
+    else if c == '-' && next == Some('-') {
+      chars.by_ref().take_while(|&c| c != '\n').for_each(drop);
+      stripped.push('\n');
+    }
+    else if c == '{' && next == Some('-') {
+      chars.next();
+      block_depth += 1;
+    }
+    else {
+      in_string = c == '"';
+      stripped.push(c);
+    }
+  }
+  stripped
+}
+
+/// The statement starting on `lines[start]`, with the lines indented deeper joined to it.
+fn continued_statement(lines: &[&str], start: usize) -> String {
+  let indent = indentation(lines[start]);
+  let mut statement = lines[start].to_string();
+  for line in &lines[start + 1..] {
+    if line.trim().is_empty() || indentation(line) <= indent {
+      break;
+    }
+    statement.push(' ');
+    statement.push_str(line.trim());
+  }
+  statement
+}
+
+fn indentation(line: &str) -> usize {
+  line.len() - line.trim_start().len()
+}
+
+fn has_word(line: &str, word: &str) -> bool {
+  line.match_indices(word).any(|(idx, _)| {
+    let before = line[..idx].chars().next_back();
+    let after = line[idx + word.len()..].chars().next();
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_' || c == '\'' || c == '"';
+    !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char)
+  })
+}
+
+fn string_literals(text: &str) -> Vec<String> {
+  tokenize(text).into_iter().filter_map(|token| match token {
+    Token::Str(s) => Some(s),
+    _ => None,
+  }).collect()
+}
+
+/// Hakyll patterns: `*` stays within a directory, `**` crosses them.
+fn glob_to_regex(pattern: &str) -> Regex {
+  let mut re = String::from("^");
+  let mut chars = pattern.chars().peekable();
+  while let Some(c) = chars.next() {
+    if c == '*' {
+      if chars.peek() == Some(&'*') {
+        chars.next();
+        re.push_str(".*");
+      }
+      else {
+        re.push_str("[^/]*");
+      }
+    }
+    else {
+      re.push_str(&regex::escape(&c.to_string()));
+    }
+  }
+  re.push('$');
+  Regex::new(&re).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{ContextField, Site};
+
+  const SITE: &str = r#"
+main :: IO ()
+main = hakyll $ do
+    match "images/*" $ do
+        route   idRoute
+        compile copyFileCompiler
+
+    -- match "drafts/*" $ do route $ constRoute "never.html"
+    match (fromList ["about.rst", "contact.markdown"]) $ do
+        route   $ setExtension "html"
+        compile $ pandocCompiler
+
+    match "posts/*" $ version "raw" $ do
+        route $ setExtension "md"
+        compile getResourceBody
+
+    match "posts/*" $ do
+        route $ gsubRoute "posts/[0-9]{4}-[0-9]{2}-[0-9]{2}-" (const "posts/")
+            `composeRoutes` setExtension "html"
+        compile $ pandocCompiler
+
+    match "notes/**" $ do
+        route $ customRoute $ (\i -> toFilePath i ++ "/index.html")
+        compile pandocCompiler
+
+    match "templates/*" $ compile templateBodyCompiler
+
+    create ["archive.html"] $ do
+        route idRoute
+
+postCtx :: Context String
+postCtx = dateField "date" "%B %e, %Y" <> teaserField "teaser" "content"
+    <> teaserFieldWithSeparator "<!--cut-->" "excerpt" "content"
+    <> constField "author" "Jasper" <> defaultContext
+
+config :: Configuration
+config = defaultConfiguration { destinationDirectory = "docs" }
+
+feedConfiguration :: FeedConfiguration
+feedConfiguration = FeedConfiguration
+    { feedTitle       = "Healthy cooking: latest recipes"
+    , feedDescription = "This feed provides fresh recipes for fresh food!"
+    , feedAuthorName  = "Jasper Van der Jeugt"
+    , feedRoot        = "http://jaspervdj.be"
+    }
+"#;
+
+  #[test]
+  fn test_route() {
+    let site = Site::parse(SITE);
+    assert_eq!(site.route("images/a.png"), Some(Ok(String::from("images/a.png"))));
+    assert_eq!(site.route("about.rst"), Some(Ok(String::from("about.html"))));
+    assert_eq!(site.route("posts/2020-02-01-bigdata.md"), Some(Ok(String::from("posts/bigdata.html"))));
+    assert_eq!(site.route("drafts/a.md"), None);
+    assert_eq!(site.route("images/sub/a.png"), None);
+    assert!(site.route("notes/a/b.md").unwrap().is_err());
+    assert!(site.route("templates/post.html").unwrap().is_err());
+  }
+
+  #[test]
+  fn test_fields() {
+    let site = Site::parse(SITE);
+    assert_eq!(site.field("destinationDirectory"), Some("docs"));
+    assert_eq!(site.field("feedTitle"), Some("Healthy cooking: latest recipes"));
+    assert_eq!(site.field("feedRoot"), Some("http://jaspervdj.be"));
+    assert_eq!(site.field("feedAuthorEmail"), None);
+  }
+
+  #[test]
+  fn test_context_fields() {
+    let site = Site::parse(SITE);
+    assert_eq!(site.context_fields, vec![
+      ContextField::Date { name: String::from("date"), format: String::from("%B %e, %Y") },
+      ContextField::Teaser { name: String::from("teaser"), separator: String::from("<!--more-->") },
+      ContextField::Teaser { name: String::from("excerpt"), separator: String::from("<!--cut-->") },
+      ContextField::Const { name: String::from("author"), value: String::from("Jasper") },
+    ]);
+    assert_eq!(site.teaser_separators(), vec!["<!--more-->", "<!--cut-->"]);
+  }
+
+  #[test]
+  fn test_warnings() {
+    let site = Site::parse(SITE);
+    assert_eq!(site.warnings.len(), 1);
+    assert!(site.warnings[0].starts_with("site.hs:22: can't interpret route"), "{}", site.warnings[0]);
+  }
+
+  #[test]
+  fn test_compose_routes_prefix() {
+    let site = Site::parse("match \"posts/*\" $ do\n  route $ composeRoutes (gsubRoute \"posts/\" (const \"blog/\")) (setExtension \".html\")\n");
+    assert_eq!(site.route("posts/foo.markdown"), Some(Ok(String::from("blog/foo.html"))));
+  }
+
+  #[test]
+  fn test_categories() {
+    assert!(Site::parse("categories <- buildCategories \"posts/*/*\" (fromCapture \"categories/*.html\")\n").categories);
+    assert!(!Site::parse(SITE).categories);
+  }
+}
@@ -0,0 +1,297 @@
+//! Hakyll templates rewritten as the tera templates zola renders, as far as the two line up.
+//! What doesn't line up is kept in a tera comment and reported.
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use regex::Regex;
+use crate::fields::{FieldMap, Mapping};
+
+/// A template rewritten for zola, along with what couldn't be.
+pub struct Translation {
+  pub tera: String,
+  /// Each with the line of the hakyll template it is about.
+  pub warnings: Vec<String>,
+}
+
+enum Block {
+  If,
+  For {
+    /// The loop variable the fields of the items are read from.
+    var: String,
+    /// Whether a `$sep$` left an `if` open to close at `$endfor$`.
+    sep: bool,
+  },
+}
+
+struct Translator<'a> {
+  tera: String,
+  warnings: Vec<String>,
+  blocks: Vec<Block>,
+  /// `section` for a template listing posts, `page` otherwise.
+  root: &'static str,
+  /// Whether `$body$` is the content of whatever template is applied within this one
+  /// rather than the content of a page.
+  layout: bool,
+  fields: &'a FieldMap,
+}
+
+/// `fields` tells what the fields hakyll contexts define become.
+pub fn translate(source: &str, fields: &FieldMap) -> Translation {
+  let mut translator = Translator {
+    tera: String::with_capacity(source.len()),
+    warnings: Vec::new(),
+    blocks: Vec::new(),
+    root: if source.contains("$for(posts)$") { "section" } else { "page" },
+    layout: source.contains("<html"),
+    fields,
+  };
+  let mut rest = source;
+  while let Some(start) = rest.find('$') {
+    translator.text(&rest[..start]);
+    let line = line_of(source, source.len() - rest.len() + start);
+    let after = &rest[start + 1..];
+    if let Some(after) = after.strip_prefix('$') {
+      translator.tera.push('$');
+      rest = after;
+      continue;
+    }
+    match after.find('$') {
+      Some(end) => {
+        translator.directive(&after[..end], line);
+        rest = &after[end + 1..];
+      },
+      None => {
+        translator.warnings.push(format!("line {}: unclosed $", line));
+        translator.text(&rest[start..]);
+        rest = "";
+      },
+    }
+  }
+  translator.text(rest);
+  if !translator.blocks.is_empty() {
+    translator.warnings.push(format!("{} $if$ or $for$ left open at the end", translator.blocks.len()));
+  }
+  Translation { tera: translator.tera, warnings: translator.warnings }
+}
+
+/// Translate the template at `input`, or every template under the directory `input`, to the
+/// same place under `output`. Returns the warnings along with the template they are about.
+pub fn translate_files(input: &Path, output: &Path, fields: &FieldMap) -> std::io::Result<Vec<(PathBuf, String)>> {
+  let mut warnings = Vec::new();
+  if input.is_dir() {
+    for entry in std::fs::read_dir(input)? {
+      let path = entry?.path();
+      let name = path.file_name().unwrap();
+      warnings.extend(translate_files(&path, &output.join(name), fields)?);
+    }
+  }
+  else {
+    let translation = translate(&std::fs::read_to_string(input)?, fields);
+    std::fs::create_dir_all(output.parent().unwrap())?;
+    std::fs::write(output, translation.tera)?;
+    warnings.extend(translation.warnings.into_iter().map(|warning| (input.to_path_buf(), warning)));
+  }
+  Ok(warnings)
+}
+
+impl <'a> Translator<'a> {
+  /// Text outside directives, where tera's own delimiters have to be escaped.
+  fn text(&mut self, text: &str) {
+    if text.contains("{{") || text.contains("{%") || text.contains("{#") {
+      self.tera.push_str("{% raw %}");
+      self.tera.push_str(text);
+      self.tera.push_str("{% endraw %}");
+    }
+    else {
+      self.tera.push_str(text);
+    }
+  }
+
+  fn directive(&mut self, directive: &str, line: usize) {
+    // `$-if(x)$` and `$endif-$` trim whitespace the way `{%-` and `-%}` do
+    let (open, directive) = match directive.strip_prefix('-') {
+      Some(directive) => ("{%-", directive),
+      None => ("{%", directive),
+    };
+    let (close, directive) = match directive.strip_suffix('-') {
+      Some(directive) => ("-%}", directive),
+      None => ("%}", directive),
+    };
+    let (name, arg) = match directive.find('(') {
+      Some(paren) if directive.ends_with(')') => (&directive[..paren], Some(directive[paren + 1..directive.len() - 1].trim())),
+      _ => (directive, None),
+    };
+    match (name, arg) {
+      ("if", Some(field)) if is_field(field) => {
+        // what is there to format is there to test
+        let condition = self.field(field).0.split('|').next().unwrap().trim().to_string();
+        self.tera.push_str(&format!("{} if {} {}", open, condition, close));
+        self.blocks.push(Block::If);
+      },
+      ("else", None) => match self.blocks.last() {
+        Some(Block::If) => self.tera.push_str(&format!("{} else {}", open, close)),
+        _ => self.unmapped(directive, line, "$else$ outside of $if$"),
+      },
+      ("endif", None) => match self.blocks.last() {
+        Some(Block::If) => {
+          self.blocks.pop();
+          self.tera.push_str(&format!("{} endif {}", open, close));
+        },
+        _ => self.unmapped(directive, line, "$endif$ outside of $if$"),
+      },
+      ("for", Some(field)) if is_field(field) => {
+        let list = if field == "posts" {
+          String::from("section.pages")
+        }
+        else {
+          self.warnings.push(format!("line {}: list field {:?} comes from site.hs, looping over {}.extra.{} instead", line, field, self.root, field));
+          self.field(field).0
+        };
+        let var = loop_var(field);
+        self.tera.push_str(&format!("{} for {} in {} {}", open, var, list, close));
+        self.blocks.push(Block::For { var, sep: false });
+      },
+      ("sep", None) => match self.blocks.last_mut() {
+        Some(Block::For { sep, .. }) if !*sep => {
+          *sep = true;
+          self.tera.push_str(&format!("{} if not loop.last {}", open, close));
+        },
+        _ => self.unmapped(directive, line, "$sep$ outside of $for$"),
+      },
+      ("endfor", None) => match self.blocks.last() {
+        Some(Block::For { sep, .. }) => {
+          if *sep {
+            self.tera.push_str("{% endif %}");
+          }
+          self.blocks.pop();
+          self.tera.push_str(&format!("{} endfor {}", open, close));
+        },
+        _ => self.unmapped(directive, line, "$endfor$ outside of $for$"),
+      },
+      ("partial", Some(path)) if path.starts_with('"') && path.ends_with('"') && path.len() > 1 => {
+        let path = &path[1..path.len() - 1];
+        let path = path.strip_prefix("templates/").unwrap_or(path);
+        self.tera.push_str(&format!("{} include {:?} {}", open, path, close));
+      },
+      ("body", None) if self.layout && self.blocks.is_empty() => {
+        self.tera.push_str("{% block content %}{% endblock content %}");
+        self.warnings.push(format!("line {}: $body$ became a block, templates applied within this one have to extend it", line));
+      },
+      (field, None) if is_field(field) => {
+        let (expr, safe) = self.field(field);
+        self.tera.push_str(&format!("{{{{ {}{} }}}}", expr, if safe { " | safe" } else { "" }));
+      },
+      _ => self.unmapped(directive, line, "no tera equivalent"),
+    }
+  }
+
+  /// Tera expression for a hakyll field, and whether it holds html to output as it is.
+  fn field(&self, name: &str) -> (String, bool) {
+    let scope = match self.blocks.iter().rev().find_map(|block| match block {
+      Block::For { var, .. } => Some(var),
+      Block::If => None,
+    }) {
+      Some(var) => var.as_str(),
+      None => self.root,
+    };
+    match self.fields.get(name) {
+      Some(Mapping::Tera { expr, safe }) => return (scoped(expr, scope), *safe),
+      Some(Mapping::Extra(_)) => return extra(scope, name),
+      None => {},
+    }
+    match name {
+      "title" | "description" | "date" => (format!("{}.{}", scope, name), false),
+      "body" => (format!("{}.content", scope), true),
+      "teaser" => (format!("{}.summary", scope), true),
+      "url" => (format!("{}.permalink", scope), false),
+      "path" => (format!("{}.relative_path", scope), false),
+      "tags" => (format!("{}.taxonomies.tags", scope), false),
+      _ => extra(scope, name),
+    }
+  }
+
+  fn unmapped(&mut self, directive: &str, line: usize, reason: &str) {
+    self.warnings.push(format!("line {}: ${}$: {}", line, directive, reason));
+    self.tera.push_str(&format!("{{# hakyll: ${}$ #}}", directive));
+  }
+}
+
+/// The field under `extra` of the page or section in `scope`.
+fn extra(scope: &str, name: &str) -> (String, bool) {
+  if name.contains('-') {
+    (format!("{}.extra[{:?}]", scope, name), false)
+  }
+  else {
+    (format!("{}.extra.{}", scope, name), false)
+  }
+}
+
+/// An expression written for `page` turned into one for `scope`.
+fn scoped(expr: &str, scope: &str) -> String {
+  static PAGE: OnceLock<Regex> = OnceLock::new();
+  PAGE.get_or_init(|| Regex::new(r"\bpage\.").unwrap()).replace_all(expr, format!("{}.", scope).as_str()).into_owned()
+}
+
+/// Hakyll field names: letters, digits, `-` and `_`.
+fn is_field(name: &str) -> bool {
+  !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+}
+
+/// `post` for `posts`.
+fn loop_var(list: &str) -> String {
+  match list.strip_suffix('s') {
+    Some(singular) if !singular.is_empty() => singular.replace('-', "_"),
+    _ => String::from("item"),
+  }
+}
+
+fn line_of(source: &str, offset: usize) -> usize {
+  source[..offset].matches('\n').count() + 1
+}
+
+#[cfg(test)]
+mod tests {
+  use super::translate;
+  use crate::fields::FieldMap;
+
+  #[test]
+  fn test_translate_post() {
+    let translation = translate("<h1>$title$</h1>\n$if(author)$by $author$$else$anonymous$endif$\n$body$\n$partial(\"templates/footer.html\")$ $$5", &FieldMap::default());
+    assert_eq!(translation.tera, "<h1>{{ page.title }}</h1>\n\
+      {% if page.extra.author %}by {{ page.extra.author }}{% else %}anonymous{% endif %}\n\
+      {{ page.content | safe }}\n\
+      {% include \"footer.html\" %} $5");
+    assert!(translation.warnings.is_empty());
+  }
+
+  #[test]
+  fn test_translate_list() {
+    let translation = translate("$for(posts)$<a href=\"$url$\">$title$</a>$sep$, $endfor$", &FieldMap::default());
+    assert_eq!(translation.tera, "{% for post in section.pages %}<a href=\"{{ post.permalink }}\">{{ post.title }}</a>\
+      {% if not loop.last %}, {% endif %}{% endfor %}");
+  }
+
+  #[test]
+  fn test_translate_layout() {
+    let translation = translate("<html><title>$title$</title>$body$</html>", &FieldMap::default());
+    assert_eq!(translation.tera, "<html><title>{{ page.title }}</title>{% block content %}{% endblock content %}</html>");
+    assert_eq!(translation.warnings.len(), 1);
+  }
+
+  #[test]
+  fn test_translate_unmapped() {
+    let translation = translate("{{x}}\n$titleize(title)$ $endif$", &FieldMap::default());
+    assert_eq!(translation.tera, "{% raw %}{{x}}\n{% endraw %}{# hakyll: $titleize(title)$ #} {# hakyll: $endif$ #}");
+    assert_eq!(translation.warnings, vec![
+      String::from("line 2: $titleize(title)$: no tera equivalent"),
+      String::from("line 2: $endif$: $endif$ outside of $if$"),
+    ]);
+  }
+
+  #[test]
+  fn test_translate_fields() {
+    let fields = FieldMap::parse("[date]\ntera = 'page.date | date(format=\"%B %e, %Y\")'\n[author]\nextra = \"Jane\"\n").unwrap();
+    let translation = translate("$for(posts)$$if(date)$$date$$endif$ $author$$endfor$", &fields);
+    assert_eq!(translation.tera, "{% for post in section.pages %}{% if post.date %}{{ post.date | date(format=\"%B %e, %Y\") }}{% endif %} \
+      {{ post.extra.author }}{% endfor %}");
+  }
+}